@@ -0,0 +1,214 @@
+use crate::gen::{GenOptions, Hints, Solver};
+use crate::search::Difficulty;
+use crate::seed::SeedCode;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// デイリーパズルの生成器のバージョン。過去の日付の盤面が変わらないように固定する
+const VERSION: u32 = 1;
+
+/// 難易度が合う seed をこの個数まで探す。見つからなければ最初の seed を使う。
+/// 2024 ~ 2025 年の日付はどれも 19 個以内で見つかる
+const MAX_TRIES: u64 = 32;
+
+/// デイリーパズルにできる盤面の大きさ
+pub const SIZES: RangeInclusive<usize> = 5..=30;
+
+/// v1 の難易度の境目。`search` の境目を調整しても過去の日付の盤面が変わらないように別に持つ
+const THRESHOLDS_V1: [f64; 2] = [0.6, 0.72];
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// グレゴリオ暦の日付 (UTC)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    /// 1970-01-01 からの日数
+    pub fn days(&self) -> i64 {
+        // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+        let y = self.year as i64 - (self.month <= 2) as i64;
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let m = self.month as i64;
+        let doy = (153 * (m + if m > 2 { -3 } else { 9 }) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
+    }
+
+    pub fn from_days(days: i64) -> Self {
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400 + (month <= 2) as i64) as i32;
+        Self { year, month, day }
+    }
+
+    /// UNIX 時刻 (秒) の UTC での日付
+    pub fn from_unix(seconds: i64) -> Self {
+        Self::from_days(seconds.div_euclid(SECONDS_PER_DAY))
+    }
+
+    /// 0 が日曜日
+    pub fn weekday(&self) -> u32 {
+        (self.days() + 4).rem_euclid(7) as u32
+    }
+}
+
+impl FromStr for Date {
+    type Err = String;
+    /// `YYYY-MM-DD` を読む
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid date: {}", s);
+        let mut parts = s.trim().splitn(3, '-');
+        let mut next = || parts.next().ok_or_else(invalid);
+        let year = next()?.parse().map_err(|_| invalid())?;
+        let month = next()?.parse().map_err(|_| invalid())?;
+        let day = next()?.parse().map_err(|_| invalid())?;
+        let date = Self { year, month, day };
+        // 2 月 30 日のような存在しない日付は弾く
+        if !(1..=12).contains(&month) || Self::from_days(date.days()) != date {
+            return Err(invalid());
+        }
+        Ok(date)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// 曜日ごとの盤面の大きさと難易度。日曜日から順に並べる
+const SCHEDULE: [(usize, Difficulty); 7] = [
+    (20, Difficulty::Hard),
+    (10, Difficulty::Easy),
+    (10, Difficulty::Medium),
+    (15, Difficulty::Easy),
+    (15, Difficulty::Medium),
+    (15, Difficulty::Hard),
+    (20, Difficulty::Medium),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DailyPuzzle {
+    pub date: Date,
+    pub size: usize,
+    pub difficulty: Difficulty,
+    pub code: SeedCode,
+}
+
+/// v1 の難易度。`Solver::steps` の数え方も過去の日付の盤面を決めるので、
+/// 数え方を変えるときは `VERSION` を上げて前の数え方をここに残す (`test_golden_daily` で確かめる)
+fn difficulty_v1(size: usize, hints: &Hints) -> Difficulty {
+    let mut solver = Solver::new(size, size, hints.clone());
+    solver.solve();
+    Difficulty::from_ratio(solver.steps as f64 / (size * size) as f64, THRESHOLDS_V1)
+}
+
+/// 日付から決まるデイリーパズル。`size` が `None` なら曜日で決める。
+/// 日付の文字列から作った seed から順に、曜日の難易度に合うものを探す
+pub fn daily_puzzle_inner(date: Date, size: Option<usize>) -> Result<DailyPuzzle, String> {
+    let (default_size, difficulty) = SCHEDULE[date.weekday() as usize];
+    let size = size.unwrap_or(default_size);
+    if !SIZES.contains(&size) {
+        return Err(format!(
+            "daily puzzle size must be between {} and {}",
+            SIZES.start(),
+            SIZES.end()
+        ));
+    }
+    let base = format!("daily-{}", date).parse::<SeedCode>().unwrap().seed;
+    let options = GenOptions::default();
+    let mut code = SeedCode {
+        version: VERSION,
+        seed: base,
+    };
+    for i in 0..MAX_TRIES {
+        let candidate = SeedCode {
+            version: VERSION,
+            seed: base.wrapping_add(i),
+        };
+        let (_, hints) = candidate
+            .generate(size, size, &options)
+            .map_err(|err| err.to_string())?;
+        if difficulty_v1(size, &hints) == difficulty {
+            code = candidate;
+            break;
+        }
+    }
+    Ok(DailyPuzzle {
+        date,
+        size,
+        difficulty,
+        code,
+    })
+}
+
+/// 次のデイリーパズル (UTC の 0 時) までの秒数
+pub fn seconds_until_next(now: i64) -> i64 {
+    SECONDS_PER_DAY - now.rem_euclid(SECONDS_PER_DAY)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::search::stats;
+
+    #[test]
+    fn test_date() {
+        let date = "2024-02-29".parse::<Date>().unwrap();
+        assert_eq!(date.to_string(), "2024-02-29");
+        assert_eq!(Date::from_days(date.days()), date);
+        assert_eq!("1970-01-01".parse::<Date>().unwrap().days(), 0);
+        // 2024-02-29 は木曜日
+        assert_eq!(date.weekday(), 4);
+        assert_eq!(Date::from_unix(1_700_000_000).to_string(), "2023-11-14");
+        assert!("2023-02-29".parse::<Date>().is_err());
+        assert!("2023-13-01".parse::<Date>().is_err());
+        assert_eq!(seconds_until_next(1_700_000_000), 86400 - 80000);
+    }
+
+    #[test]
+    fn test_daily_puzzle() {
+        // 月曜日
+        let date = "2024-03-04".parse::<Date>().unwrap();
+        let daily = daily_puzzle_inner(date, None).unwrap();
+        assert_eq!((daily.size, daily.difficulty), (10, Difficulty::Easy));
+        assert_eq!(daily, daily_puzzle_inner(date, None).unwrap());
+        let (grid, hints) = daily.code.generate(10, 10, &GenOptions::default()).unwrap();
+        assert_eq!(stats(10, 10, &grid, &hints).difficulty, Difficulty::Easy);
+
+        let other = daily_puzzle_inner("2024-03-05".parse().unwrap(), Some(10)).unwrap();
+        assert_ne!(daily.code, other.code);
+        assert!(daily_puzzle_inner(date, Some(1)).is_err());
+        assert!(daily_puzzle_inner(date, Some(1000)).is_err());
+    }
+
+    #[test]
+    fn test_golden_daily() {
+        // 過去の日付の盤面が変わらないように、10x10 と 15x15 の数日分の seed を固定する
+        let golden = [
+            ("2024-03-04", "v1-4307747333397559225"),
+            ("2024-03-05", "v1-4307748432909187436"),
+            ("2024-03-06", "v1-4307749532420815647"),
+            ("2024-03-07", "v1-4307750631932443857"),
+        ];
+        for (date, code) in golden {
+            let daily = daily_puzzle_inner(date.parse().unwrap(), None).unwrap();
+            assert_eq!(daily.code.to_string(), code, "{}", date);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+/// 高さ 7 マスのビットマップフォント。`#` が塗るマス。
+pub const HEIGHT: usize = 7;
+
+type Glyph = [&'static str; HEIGHT];
+
+#[rustfmt::skip]
+const GLYPHS: &[(char, Glyph)] = &[
+    ('A', [".###.", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"]),
+    ('B', ["####.", "#...#", "#...#", "####.", "#...#", "#...#", "####."]),
+    ('C', [".###.", "#...#", "#....", "#....", "#....", "#...#", ".###."]),
+    ('D', ["####.", "#...#", "#...#", "#...#", "#...#", "#...#", "####."]),
+    ('E', ["#####", "#....", "#....", "####.", "#....", "#....", "#####"]),
+    ('F', ["#####", "#....", "#....", "####.", "#....", "#....", "#...."]),
+    ('G', [".###.", "#...#", "#....", "#.###", "#...#", "#...#", ".####"]),
+    ('H', ["#...#", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"]),
+    ('I', [".###.", "..#..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('J', ["..###", "...#.", "...#.", "...#.", "...#.", "#..#.", ".##.."]),
+    ('K', ["#...#", "#..#.", "#.#..", "##...", "#.#..", "#..#.", "#...#"]),
+    ('L', ["#....", "#....", "#....", "#....", "#....", "#....", "#####"]),
+    ('M', ["#...#", "##.##", "#.#.#", "#.#.#", "#...#", "#...#", "#...#"]),
+    ('N', ["#...#", "#...#", "##..#", "#.#.#", "#..##", "#...#", "#...#"]),
+    ('O', [".###.", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."]),
+    ('P', ["####.", "#...#", "#...#", "####.", "#....", "#....", "#...."]),
+    ('Q', [".###.", "#...#", "#...#", "#...#", "#.#.#", "#..#.", ".##.#"]),
+    ('R', ["####.", "#...#", "#...#", "####.", "#.#..", "#..#.", "#...#"]),
+    ('S', [".####", "#....", "#....", ".###.", "....#", "....#", "####."]),
+    ('T', ["#####", "..#..", "..#..", "..#..", "..#..", "..#..", "..#.."]),
+    ('U', ["#...#", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."]),
+    ('V', ["#...#", "#...#", "#...#", "#...#", "#...#", ".#.#.", "..#.."]),
+    ('W', ["#...#", "#...#", "#...#", "#.#.#", "#.#.#", "#.#.#", ".#.#."]),
+    ('X', ["#...#", "#...#", ".#.#.", "..#..", ".#.#.", "#...#", "#...#"]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#..", "..#.."]),
+    ('Z', ["#####", "....#", "...#.", "..#..", ".#...", "#....", "#####"]),
+    ('0', [".###.", "#...#", "#..##", "#.#.#", "##..#", "#...#", ".###."]),
+    ('1', ["..#..", ".##..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('2', [".###.", "#...#", "....#", "...#.", "..#..", ".#...", "#####"]),
+    ('3', ["#####", "...#.", "..#..", "...#.", "....#", "#...#", ".###."]),
+    ('4', ["...#.", "..##.", ".#.#.", "#..#.", "#####", "...#.", "...#."]),
+    ('5', ["#####", "#....", "####.", "....#", "....#", "#...#", ".###."]),
+    ('6', ["..##.", ".#...", "#....", "####.", "#...#", "#...#", ".###."]),
+    ('7', ["#####", "....#", "...#.", "..#..", ".#...", ".#...", ".#..."]),
+    ('8', [".###.", "#...#", "#...#", ".###.", "#...#", "#...#", ".###."]),
+    ('9', [".###.", "#...#", "#...#", ".####", "....#", "...#.", ".##.."]),
+    (' ', ["...", "...", "...", "...", "...", "...", "..."]),
+    ('!', ["#", "#", "#", "#", "#", ".", "#"]),
+    ('?', [".###.", "#...#", "....#", "...#.", "..#..", ".....", "..#.."]),
+    ('.', [".", ".", ".", ".", ".", ".", "#"]),
+    (',', ["..", "..", "..", "..", "..", ".#", "#."]),
+    (':', [".", ".", "#", ".", ".", "#", "."]),
+    ('\'', ["#", "#", ".", ".", ".", ".", "."]),
+    ('-', ["...", "...", "...", "###", "...", "...", "..."]),
+    ('+', [".....", "..#..", "..#..", "#####", "..#..", "..#..", "....."]),
+    ('♥', [".....", ".#.#.", "#####", "#####", ".###.", "..#..", "....."]),
+    ('ア', ["#####", "....#", "..##.", "..#..", "..#..", ".#...", "#...."]),
+    ('イ', ["....#", "...#.", "..##.", ".#.#.", "#..#.", "...#.", "...#."]),
+    ('ウ', ["..#..", "#####", "#...#", "....#", "...#.", "..#..", ".#..."]),
+    ('エ', [".....", "#####", "..#..", "..#..", "..#..", "..#..", "#####"]),
+    ('オ', ["...#.", "#####", "...#.", "..##.", ".#.#.", "#..#.", "..##."]),
+    ('カ', ["..#..", "#####", "..#.#", "..#.#", ".#..#", ".#..#", "#..#."]),
+    ('キ', ["..#..", "#####", "..#..", "#####", "..#..", "..#..", "..#.."]),
+    ('ク', [".#...", ".####", "#...#", "....#", "...#.", "..#..", "##..."]),
+    ('コ', [".....", "#####", "....#", "....#", "....#", "....#", "#####"]),
+    ('サ', [".#.#.", "#####", ".#.#.", ".#.#.", "...#.", "..#..", ".#..."]),
+    ('シ', ["#....", ".#..#", "#...#", ".#..#", "....#", "...#.", "###.."]),
+    ('ス', ["#####", "....#", "...#.", "..#..", ".#.#.", "#...#", "....."]),
+    ('セ', [".#...", ".#...", "#####", ".#..#", ".#.#.", ".#...", "..###"]),
+    ('ソ', ["#...#", "#...#", ".#..#", "....#", "...#.", "..#..", "##..."]),
+    ('タ', [".#...", ".####", "#...#", ".#.#.", "..#..", ".#.#.", "#...."]),
+    ('ツ', ["#.#.#", "#.#.#", "....#", "....#", "...#.", "..#..", "##..."]),
+    ('テ', ["#####", ".....", "#####", "..#..", "..#..", ".#...", "#...."]),
+    ('ト', [".#...", ".#...", ".##..", ".#.#.", ".#...", ".#...", ".#..."]),
+    ('ナ', ["..#..", "#####", "..#..", "..#..", "..#..", ".#...", "#...."]),
+    ('ニ', [".....", ".###.", ".....", ".....", ".....", "#####", "....."]),
+    ('ノ', ["....#", "....#", "...#.", "...#.", "..#..", ".#...", "#...."]),
+    ('ハ', [".#.#.", ".#.#.", ".#..#", "#...#", "#...#", "#...#", "....."]),
+    ('ヒ', ["#....", "#...#", "###..", "#....", "#....", "#....", ".####"]),
+    ('フ', ["#####", "....#", "....#", "...#.", "...#.", "..#..", "##..."]),
+    ('ホ', ["..#..", "#####", "..#..", "#.#.#", "#.#.#", "..#..", ".##.."]),
+    ('マ', ["#####", "....#", "...#.", ".##..", "..#..", "...#.", "....."]),
+    ('メ', ["....#", "#..#.", ".##..", "..#..", ".#.#.", "#....", "....."]),
+    ('ラ', [".###.", ".....", "#####", "....#", "...#.", "..#..", ".#..."]),
+    ('リ', ["#...#", "#...#", "#...#", "#...#", "....#", "...#.", "..#.."]),
+    ('ル', [".#.#.", ".#.#.", ".#.#.", ".#.#.", ".#.#.", ".#.##", "#..#."]),
+    ('レ', ["#....", "#....", "#....", "#....", "#...#", "#..#.", "###.."]),
+    ('ロ', [".....", "#####", "#...#", "#...#", "#...#", "#####", "....."]),
+    ('ワ', ["#####", "#...#", "....#", "....#", "...#.", "..#..", "##..."]),
+    ('ン', ["#....", ".#..#", "....#", "....#", "...#.", "..#..", "##..."]),
+    ('ー', [".....", ".....", ".....", "#####", ".....", ".....", "....."]),
+    ('ャ', [".....", ".....", ".#...", ".####", ".#.#.", "..#..", "..#.."]),
+    ('ュ', [".....", ".....", ".....", ".###.", "...#.", "...#.", "#####"]),
+    ('ョ', [".....", ".....", ".###.", "...#.", ".###.", "...#.", ".###."]),
+    ('ッ', [".....", ".....", ".....", "#.#.#", "....#", "...#.", ".##.."]),
+];
+
+/// 濁点。清音の字形の右に付け足す
+const DAKUTEN: Glyph = [".#.#", ".#.#", "....", "....", "....", "....", "...."];
+
+/// 文字の字形を返す。英小文字は大文字に、ひらがなはカタカナに読み替える
+pub fn glyph(c: char) -> Option<Vec<Vec<bool>>> {
+    let c = c.to_ascii_uppercase();
+    // ひらがな → カタカナ
+    let c = match c {
+        'ぁ'..='ゖ' => char::from_u32(c as u32 + 0x60).unwrap(),
+        _ => c,
+    };
+    let to_bits = |glyph: &Glyph| {
+        glyph
+            .iter()
+            .map(|row| row.bytes().map(|c| c == b'#').collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+    };
+    if let Some((_, glyph)) = GLYPHS.iter().find(|&&(g, _)| g == c) {
+        return Some(to_bits(glyph));
+    }
+    // 濁音は清音 (コードポイントが 1 つ前) + 濁点
+    let voiced = "ガギグゲゴザジズゼゾダヂヅデドバビブベボ";
+    if voiced.contains(c) {
+        let mut bits = glyph(char::from_u32(c as u32 - 1).unwrap())?;
+        for (row, dakuten) in bits.iter_mut().zip(to_bits(&DAKUTEN)) {
+            row.extend(dakuten);
+        }
+        return Some(bits);
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_glyphs() {
+        for (c, glyph) in GLYPHS {
+            assert!(glyph.iter().all(|row| row.len() == glyph[0].len()), "{}", c);
+        }
+        assert_eq!(glyph('g'), glyph('G'));
+        assert_eq!(glyph('す'), glyph('ス'));
+        assert_eq!(glyph('ギ').unwrap()[0].len(), 9);
+        assert!(glyph('ゲ').is_none());
+    }
+}
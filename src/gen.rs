@@ -4,36 +4,555 @@ use std::fmt;
 use std::{cmp::Ordering, collections::VecDeque};
 
 pub type Hints = (Vec<Vec<usize>>, Vec<Vec<usize>>);
-pub fn gen_inner(h: usize, w: usize, seed: u64) -> (Vec<bool>, Hints) {
+
+/// 盤面の対称性。代表マスだけを乱択し、残りは写して埋める。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    None,
+    /// 左右反転
+    Horizontal,
+    /// 上下反転
+    Vertical,
+    /// 180° 回転
+    Rotational,
+    /// 主対角線で反転（正方形のみ）
+    Diagonal,
+    /// 左右反転 + 上下反転
+    Quarter,
+}
+
+impl Symmetry {
+    /// (y, x) と同じ色になるマスのうち、行優先で最初に現れるもの
+    fn representative(self, h: usize, w: usize, y: usize, x: usize) -> (usize, usize) {
+        match self {
+            Symmetry::None => (y, x),
+            Symmetry::Horizontal => (y, x.min(w - 1 - x)),
+            Symmetry::Vertical => (y.min(h - 1 - y), x),
+            Symmetry::Rotational => (y, x).min((h - 1 - y, w - 1 - x)),
+            Symmetry::Diagonal => (y, x).min((x, y)),
+            Symmetry::Quarter => (y.min(h - 1 - y), x.min(w - 1 - x)),
+        }
+    }
+}
+
+/// 盤面の元になる絵の作り方
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pattern {
+    /// 各マスを独立に 1/2 で塗る
+    Random,
+    /// 平滑化した value noise を閾値で切る
+    Noise,
+    /// ランダムな盤面にセル・オートマトンの多数決を繰り返す
+    Automaton,
+    /// 種マスから連結な塊を成長させる
+    Blob,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GenOptions {
+    pub symmetry: Symmetry,
+    pub pattern: Pattern,
+    /// `smoothness` がこれ未満の盤面は一意性判定の前に捨てる。0 ~ 1
+    pub min_smoothness: f64,
+    pub constraints: Constraints,
+}
+
+impl GenOptions {
+    fn validate(&self, h: usize, w: usize) -> Result<(), GenError> {
+        if !(0.0..=1.0).contains(&self.min_smoothness) {
+            return Err(GenError::Conflicting(
+                "min_smoothness is not between 0 and 1",
+            ));
+        }
+        self.constraints.validate(h, w)
+    }
+}
+
+impl Default for GenOptions {
+    fn default() -> Self {
+        Self {
+            symmetry: Symmetry::None,
+            pattern: Pattern::Random,
+            min_smoothness: 0.0,
+            constraints: Constraints::default(),
+        }
+    }
+}
+
+/// 手がかりに対する条件。`None` の項目は問わない
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Constraints {
+    /// 1 列あたりの手がかりの個数の最大
+    pub max_clues_per_line: Option<usize>,
+    /// 手がかりの数字の最大
+    pub max_run: Option<usize>,
+    /// 手がかりの数字の最小
+    pub min_run: Option<usize>,
+    /// 手がかり全体のうち 1 の割合の最大
+    pub max_single_fraction: Option<f64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GenError {
+    /// 条件どうし、または条件と盤面の大きさが矛盾している
+    Conflicting(&'static str),
+    /// 試行回数の上限までに条件を満たす盤面が見つからなかった
+    Exhausted,
+    /// 対角線での反転は正方形の盤面にしか使えない
+    NotSquare,
+    /// seed の生成器のバージョンを知らない
+    UnknownVersion(u32),
+}
+
+impl fmt::Display for GenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenError::Conflicting(reason) => write!(f, "conflicting constraints: {}", reason),
+            GenError::Exhausted => write!(
+                f,
+                "no puzzle satisfies the constraints within {} attempts",
+                MAX_ATTEMPTS
+            ),
+            GenError::NotSquare => write!(f, "diagonal symmetry needs a square board"),
+            GenError::UnknownVersion(v) => write!(f, "unknown generator version: v{}", v),
+        }
+    }
+}
+
+/// 条件や `min_smoothness` があるときに盤面を乱択する回数の上限
+const MAX_ATTEMPTS: usize = 2000;
+/// 1 つの盤面を条件に近づける回数の上限
+const REPAIR_ROUNDS: usize = 10;
+
+impl Constraints {
+    pub fn is_active(&self) -> bool {
+        *self != Self::default()
+    }
+
+    /// 満たす盤面が明らかに存在しない条件を弾く。
+    /// 全部塗る列や空の列は作らないので、各列に長さ 1 ~ n - 1 のブロックが必要になる
+    fn validate(&self, h: usize, w: usize) -> Result<(), GenError> {
+        if self.max_clues_per_line == Some(0) {
+            return Err(GenError::Conflicting("max_clues_per_line is 0"));
+        }
+        if self.max_run == Some(0) {
+            return Err(GenError::Conflicting("max_run is 0"));
+        }
+        if let (Some(min), Some(max)) = (self.min_run, self.max_run) {
+            if min > max {
+                return Err(GenError::Conflicting("min_run exceeds max_run"));
+            }
+        }
+        if self.min_run.is_some_and(|min| min >= h.min(w)) {
+            return Err(GenError::Conflicting("min_run does not fit in a line"));
+        }
+        if self.max_single_fraction.is_some_and(f64::is_nan) {
+            return Err(GenError::Conflicting("max_single_fraction is not a number"));
+        }
+        if self.max_single_fraction.is_some_and(|f| f < 0.0) {
+            return Err(GenError::Conflicting("max_single_fraction is negative"));
+        }
+        Ok(())
+    }
+
+    pub fn satisfied(&self, hints: &Hints) -> bool {
+        let lines = || hints.0.iter().chain(hints.1.iter());
+        let clues = || lines().flatten().copied();
+        let total = clues().count();
+        let singles = clues().filter(|&c| c == 1).count();
+        self.max_clues_per_line
+            .is_none_or(|max| lines().all(|line| line.len() <= max))
+            && self.max_run.is_none_or(|max| clues().all(|c| c <= max))
+            && self.min_run.is_none_or(|min| clues().all(|c| c >= min))
+            && self
+                .max_single_fraction
+                .is_none_or(|f| total == 0 || singles as f64 <= f * total as f64)
+    }
+
+    /// 1 列を条件に近づける。短いブロックは伸ばし、長いブロックは切り、
+    /// ブロックが多すぎれば隙間の狭いものからつなげる
+    fn repair_line(&self, line: &mut [bool]) {
+        let n = line.len();
+        for _ in 0..2 * n {
+            let runs = runs(line);
+            if let Some(min) = self.min_run {
+                if let Some(&(start, _)) = runs.iter().find(|&&(_, len)| len < min) {
+                    let start = start.min(n - min);
+                    line[start..start + min].fill(true);
+                    continue;
+                }
+            }
+            if let Some(fraction) = self.max_single_fraction {
+                // 1 が多すぎる列では 1 のブロックを 2 に伸ばす
+                let singles = runs.iter().filter(|run| run.1 == 1).count();
+                if singles as f64 > fraction * runs.len() as f64 && n >= 2 {
+                    let &(start, _) = runs.iter().find(|run| run.1 == 1).unwrap();
+                    let start = start.min(n - 2);
+                    line[start..start + 2].fill(true);
+                    continue;
+                }
+            }
+            if let Some(max) = self.max_run {
+                if let Some(&(start, _)) = runs.iter().find(|&&(_, len)| len > max) {
+                    line[start + max] = false;
+                    continue;
+                }
+            }
+            if let Some(max) = self.max_clues_per_line {
+                if runs.len() > max {
+                    let merged = runs
+                        .iter()
+                        .tuple_windows()
+                        .filter(|(a, b)| self.max_run.is_none_or(|max| b.0 + b.1 - a.0 <= max))
+                        .min_by_key(|(a, b)| b.0 - a.0 - a.1);
+                    if let Some((a, b)) = merged {
+                        line[a.0..b.0].fill(true);
+                    } else {
+                        let &(start, len) = runs.iter().min_by_key(|run| run.1).unwrap();
+                        line[start..start + len].fill(false);
+                    }
+                    continue;
+                }
+            }
+            break;
+        }
+    }
+
+    /// 行、列の順に `repair_line` をかけて対称性を戻す、を条件を満たすまで何度か繰り返す
+    fn repair(&self, grid: &mut [Vec<Option<bool>>], symmetry: Symmetry) {
+        let (h, w) = (grid.len(), grid[0].len());
+        for _ in 0..REPAIR_ROUNDS {
+            let mut plain = grid
+                .iter()
+                .map(|row| row.iter().map(|&f| f == Some(true)).collect_vec())
+                .collect_vec();
+            for row in plain.iter_mut() {
+                self.repair_line(row);
+            }
+            for x in 0..w {
+                let mut column = (0..h).map(|y| plain[y][x]).collect_vec();
+                self.repair_line(&mut column);
+                for (row, f) in plain.iter_mut().zip(column) {
+                    row[x] = f;
+                }
+            }
+            for (y, row) in grid.iter_mut().enumerate() {
+                for (x, cell) in row.iter_mut().enumerate() {
+                    let (ry, rx) = symmetry.representative(h, w, y, x);
+                    *cell = Some(plain[ry][rx]);
+                }
+            }
+            if self.satisfied(&Board::new(h, w, grid.to_vec()).get_hints()) {
+                break;
+            }
+        }
+    }
+}
+
+fn sample_grid<R: Rng>(
+    h: usize,
+    w: usize,
+    symmetry: Symmetry,
+    rng: &mut R,
+) -> Vec<Vec<Option<bool>>> {
+    let mut grid = vec![vec![None; w]; h];
+    for y in 0..h {
+        for x in 0..w {
+            let (ry, rx) = symmetry.representative(h, w, y, x);
+            grid[y][x] = if (ry, rx) == (y, x) {
+                Some(rng.gen_bool(0.5))
+            } else {
+                grid[ry][rx]
+            };
+        }
+    }
+    grid
+}
+
+fn sample_pattern<R: Rng>(
+    h: usize,
+    w: usize,
+    options: &GenOptions,
+    rng: &mut R,
+) -> Vec<Vec<Option<bool>>> {
+    let grid = match options.pattern {
+        Pattern::Random => return sample_grid(h, w, options.symmetry, rng),
+        Pattern::Noise => noise_grid(h, w, rng),
+        Pattern::Automaton => automaton_grid(h, w, rng),
+        Pattern::Blob => blob_grid(h, w, rng),
+    };
+    (0..h)
+        .map(|y| {
+            (0..w)
+                .map(|x| {
+                    let (ry, rx) = options.symmetry.representative(h, w, y, x);
+                    Some(grid[ry][rx])
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn noise_grid<R: Rng>(h: usize, w: usize, rng: &mut R) -> Vec<Vec<bool>> {
+    let smoothstep = |t: f64| t * t * (3.0 - 2.0 * t);
+    let mut field = vec![vec![0.0; w]; h];
+    let mut scale = (h.max(w) as f64 / 3.0).max(2.0);
+    let mut amplitude = 1.0;
+    for _ in 0..2 {
+        let gh = (h as f64 / scale) as usize + 2;
+        let gw = (w as f64 / scale) as usize + 2;
+        let lattice = (0..gh)
+            .map(|_| (0..gw).map(|_| rng.gen::<f64>()).collect_vec())
+            .collect_vec();
+        for (y, row) in field.iter_mut().enumerate() {
+            for (x, value) in row.iter_mut().enumerate() {
+                let (fy, fx) = (y as f64 / scale, x as f64 / scale);
+                let (iy, ix) = (fy as usize, fx as usize);
+                let (ty, tx) = (smoothstep(fy - iy as f64), smoothstep(fx - ix as f64));
+                let top = lattice[iy][ix] * (1.0 - tx) + lattice[iy][ix + 1] * tx;
+                let bottom = lattice[iy + 1][ix] * (1.0 - tx) + lattice[iy + 1][ix + 1] * tx;
+                *value += amplitude * (top * (1.0 - ty) + bottom * ty);
+            }
+        }
+        scale = (scale / 2.0).max(1.0);
+        amplitude /= 2.0;
+    }
+
+    // 値の大きい順に密度ぶんだけ塗る
+    let count = ((h * w) as f64 * rng.gen_range(0.4..0.6)) as usize;
+    let mut grid = vec![vec![false; w]; h];
+    for (y, x) in (0..h)
+        .cartesian_product(0..w)
+        .sorted_by(|&(y0, x0), &(y1, x1)| field[y1][x1].partial_cmp(&field[y0][x0]).unwrap())
+        .take(count)
+    {
+        grid[y][x] = true;
+    }
+    grid
+}
+
+fn automaton_grid<R: Rng>(h: usize, w: usize, rng: &mut R) -> Vec<Vec<bool>> {
+    let mut grid = (0..h)
+        .map(|_| (0..w).map(|_| rng.gen_bool(0.5)).collect_vec())
+        .collect_vec();
+    for _ in 0..4 {
+        // 3x3 近傍（盤外は数えない）の多数決
+        grid = (0..h)
+            .map(|y| {
+                (0..w)
+                    .map(|x| {
+                        let (mut filled, mut total) = (0, 0);
+                        for row in &grid[y.saturating_sub(1)..(y + 2).min(h)] {
+                            for &f in &row[x.saturating_sub(1)..(x + 2).min(w)] {
+                                filled += f as usize;
+                                total += 1;
+                            }
+                        }
+                        filled * 2 > total
+                    })
+                    .collect_vec()
+            })
+            .collect_vec();
+    }
+    grid
+}
+
+fn blob_grid<R: Rng>(h: usize, w: usize, rng: &mut R) -> Vec<Vec<bool>> {
+    let target = ((h * w) as f64 * rng.gen_range(0.4..0.6)) as usize;
+    let mut grid = vec![vec![false; w]; h];
+    let mut frontier = Vec::<(usize, usize)>::new();
+    for _ in 0..rng.gen_range(1..=3) {
+        frontier.push((rng.gen_range(0..h), rng.gen_range(0..w)));
+    }
+    let mut count = 0;
+    while count < target && !frontier.is_empty() {
+        let (y, x) = frontier.swap_remove(rng.gen_range(0..frontier.len()));
+        if grid[y][x] {
+            continue;
+        }
+        grid[y][x] = true;
+        count += 1;
+        for (dy, dx) in [(0, 1), (1, 0), (0, !0), (!0, 0)] {
+            let (ny, nx) = (y.wrapping_add(dy), x.wrapping_add(dx));
+            if ny < h && nx < w && !grid[ny][nx] {
+                frontier.push((ny, nx));
+            }
+        }
+    }
+    grid
+}
+
+/// 上下左右に隣り合うマスの組のうち、同じ色の組の割合
+pub fn smoothness(grid: &[Vec<Option<bool>>]) -> f64 {
+    let (h, w) = (grid.len(), grid[0].len());
+    let mut same = 0;
+    let mut total = 0;
+    for y in 0..h {
+        for x in 0..w {
+            if x + 1 < w {
+                same += (grid[y][x] == grid[y][x + 1]) as usize;
+                total += 1;
+            }
+            if y + 1 < h {
+                same += (grid[y][x] == grid[y + 1][x]) as usize;
+                total += 1;
+            }
+        }
+    }
+    if total == 0 {
+        1.0
+    } else {
+        same as f64 / total as f64
+    }
+}
+
+/// 塗られたブロックの (開始位置, 長さ)
+pub fn runs(line: &[bool]) -> Vec<(usize, usize)> {
+    let mut ret = Vec::new();
+    let mut i = 0;
+    while i < line.len() {
+        let mut j = i;
+        while j < line.len() && line[i] == line[j] {
+            j += 1;
+        }
+        if line[i] {
+            ret.push((i, j - i));
+        }
+        i = j;
+    }
+    ret
+}
+
+/// 条件 `options.constraints` を満たし、一意に解ける盤面を作る。
+/// 条件も `min_smoothness` もなければ必ず見つかるまで乱択を続ける。
+/// v1 の seed の盤面はこの関数で決まるので、出力が変わる変更をするときは
+/// 先に今の実装を `seed::gen_v1` に写して v1 の出力を保つこと
+pub fn gen_inner(
+    h: usize,
+    w: usize,
+    seed: u64,
+    options: &GenOptions,
+) -> Result<(Vec<bool>, Hints), GenError> {
+    if options.symmetry == Symmetry::Diagonal && h != w {
+        return Err(GenError::NotSquare);
+    }
+    let constraints = &options.constraints;
+    let active = constraints.is_active();
+    options.validate(h, w)?;
     let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(seed);
+    let mut attempts = 0;
     let (grid, hints) = loop {
-        let mut board = Board::new(
-            h,
-            w,
-            (0..h)
-                .map(|_| (0..w).map(|_| Some(rng.gen_bool(0.5))).collect())
-                .collect(),
-        );
+        if active || options.min_smoothness > 0.0 {
+            if attempts == MAX_ATTEMPTS {
+                return Err(GenError::Exhausted);
+            }
+            attempts += 1;
+        }
+        let mut grid = sample_pattern(h, w, options, &mut rng);
+        if active {
+            constraints.repair(&mut grid, options.symmetry);
+        }
+        if smoothness(&grid) < options.min_smoothness {
+            continue;
+        }
+        let mut board = Board::new(h, w, grid);
         let hints = board.get_hints();
+        if !constraints.satisfied(&hints) {
+            continue;
+        }
 
         let mut full_or_empty_line_doesnt_exist = true;
-        full_or_empty_line_doesnt_exist &= hints.0.iter().all(|hint| hint.get(0) != Some(&w));
+        full_or_empty_line_doesnt_exist &= hints.0.iter().all(|hint| hint.first() != Some(&w));
         full_or_empty_line_doesnt_exist &= hints.0.iter().all(|hint| !hint.is_empty());
-        full_or_empty_line_doesnt_exist &= hints.1.iter().all(|hint| hint.get(0) != Some(&h));
+        full_or_empty_line_doesnt_exist &= hints.1.iter().all(|hint| hint.first() != Some(&h));
         full_or_empty_line_doesnt_exist &= hints.1.iter().all(|hint| !hint.is_empty());
+        if !full_or_empty_line_doesnt_exist {
+            continue;
+        }
 
         let mut solver = Solver::new(h, w, hints.clone());
-        if solver.solve() && full_or_empty_line_doesnt_exist {
+        if solver.solve() {
             break (solver.board.get_plain(), hints);
         }
     };
-    (grid, hints)
+    Ok((grid, hints))
 }
 
-struct Board {
+/// 手がかりと、最初から決まっている (塗られた / ×の付いた) マスの組
+pub struct Puzzle {
+    pub hints: Hints,
+    pub givens: Vec<(usize, usize, bool)>,
+}
+
+impl Puzzle {
+    /// 絵から手がかりを作る。`with_givens` なら、手がかりだけで解けないときに少数のマスを与える
+    pub fn from_grid(grid: &[Vec<bool>], with_givens: bool) -> Self {
+        let (h, w) = (grid.len(), grid[0].len());
+        let mut board = Board::new(
+            h,
+            w,
+            grid.iter()
+                .map(|row| row.iter().map(|&f| Some(f)).collect())
+                .collect(),
+        );
+        let hints = board.get_hints();
+        let givens = if with_givens {
+            find_givens(grid, &hints)
+        } else {
+            vec![]
+        };
+        Self { hints, givens }
+    }
+}
+
+/// 手がかりだけでは解けない盤面に、解けるようになるまでマスを与える。
+/// 与えたときに新しく決まるマスが最も多いものを貪欲に選び、最後に不要になったものを取り除く。
+pub fn find_givens(grid: &[Vec<bool>], hints: &Hints) -> Vec<(usize, usize, bool)> {
+    let (h, w) = (grid.len(), grid[0].len());
+    let solve = |givens: &[(usize, usize, bool)]| {
+        let mut solver = Solver::with_givens(h, w, hints.clone(), givens);
+        let solved = solver.solve();
+        (solved, solver)
+    };
+    let determined = |solver: &Solver| solver.board.grid.iter().flatten().flatten().count();
+
+    let mut givens = vec![];
+    loop {
+        let (solved, solver) = solve(&givens);
+        if solved {
+            break;
+        }
+        let undetermined = (0..h)
+            .cartesian_product(0..w)
+            .filter(|&(y, x)| solver.board.grid[y][x].is_none())
+            .collect_vec();
+        // 候補が多いときは等間隔に間引いて調べる
+        let step = (undetermined.len() / 32).max(1);
+        let (y, x) = undetermined
+            .into_iter()
+            .step_by(step)
+            .max_by_key(|&(y, x)| {
+                let mut candidate = givens.clone();
+                candidate.push((y, x, grid[y][x]));
+                determined(&solve(&candidate).1)
+            })
+            .unwrap();
+        givens.push((y, x, grid[y][x]));
+    }
+    for i in (0..givens.len()).rev() {
+        let mut rest = givens.clone();
+        rest.remove(i);
+        if solve(&rest).0 {
+            givens = rest;
+        }
+    }
+    givens
+}
+
+pub struct Board {
     h: usize,
     w: usize,
-    grid: Vec<Vec<Option<bool>>>,
+    pub grid: Vec<Vec<Option<bool>>>,
 }
 impl fmt::Debug for Board {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -56,7 +575,7 @@ impl fmt::Debug for Board {
 }
 
 impl Board {
-    fn new(h: usize, w: usize, grid: Vec<Vec<Option<bool>>>) -> Self {
+    pub fn new(h: usize, w: usize, grid: Vec<Vec<Option<bool>>>) -> Self {
         Self { h, w, grid }
     }
     fn set(&mut self, y: usize, x: usize, val: Option<bool>) {
@@ -92,10 +611,10 @@ impl Board {
         }
         ret
     }
-    fn get_plain(&self) -> Vec<bool> {
+    pub fn get_plain(&self) -> Vec<bool> {
         self.grid.iter().flatten().map(|&f| f.unwrap()).collect()
     }
-    fn get_hints(&mut self) -> Hints {
+    pub fn get_hints(&mut self) -> Hints {
         let hints_horizontal = (0..self.h)
             .map(|y| self.compress_horizontal(y))
             .collect::<Vec<_>>();
@@ -106,18 +625,30 @@ impl Board {
     }
 }
 
-struct Solver {
+pub struct Solver {
     hints: Hints,
-    board: Board,
+    pub board: Board,
+    /// 列を調べ直した回数。難しさの目安
+    pub steps: usize,
 }
 impl Solver {
-    fn new(h: usize, w: usize, hints: Hints) -> Self {
+    pub fn new(h: usize, w: usize, hints: Hints) -> Self {
         Self {
             hints,
             board: Board::new(h, w, vec![vec![None; w]; h]),
+            steps: 0,
         }
     }
 
+    /// 一部のマスが最初から決まっている盤面を解く
+    pub fn with_givens(h: usize, w: usize, hints: Hints, givens: &[(usize, usize, bool)]) -> Self {
+        let mut solver = Self::new(h, w, hints);
+        for &(y, x, val) in givens {
+            solver.board.set(y, x, Some(val));
+        }
+        solver
+    }
+
     fn set(
         &mut self,
         y: usize,
@@ -140,7 +671,7 @@ impl Solver {
         }
     }
 
-    fn solve(&mut self) -> bool {
+    pub fn solve(&mut self) -> bool {
         let mut q = VecDeque::<(usize, bool)>::new();
         let mut used = (vec![false; self.board.h], vec![false; self.board.w]);
 
@@ -150,8 +681,22 @@ impl Solver {
         for x in 0..self.board.w {
             self.solve_line_initial_vertical(x, &mut q, &mut used);
         }
+        // 最初から決まっていたマスを含む列もキューに入れる
+        for y in 0..self.board.h {
+            if !used.0[y] && self.board.grid[y].iter().any(|f| f.is_some()) {
+                q.push_back((y, true));
+                used.0[y] = true;
+            }
+        }
+        for x in 0..self.board.w {
+            if !used.1[x] && (0..self.board.h).any(|y| self.board.grid[y][x].is_some()) {
+                q.push_back((x, false));
+                used.1[x] = true;
+            }
+        }
 
         while let Some((i, is_horizontal)) = q.pop_front() {
+            self.steps += 1;
             if is_horizontal {
                 let y = i;
                 used.0[y] = false;
@@ -171,6 +716,12 @@ impl Solver {
         q: &mut VecDeque<(usize, bool)>,
         used: &mut (Vec<bool>, Vec<bool>),
     ) {
+        if self.hints.0[y].is_empty() {
+            for x in 0..self.board.w {
+                self.set(y, x, Some(false), q, used);
+            }
+            return;
+        }
         let mut l_sum = 0_usize;
         let mut r_sum = self.hints.0[y].iter().sum::<usize>();
         let n = self.hints.0[y].len();
@@ -196,6 +747,12 @@ impl Solver {
         q: &mut VecDeque<(usize, bool)>,
         used: &mut (Vec<bool>, Vec<bool>),
     ) {
+        if self.hints.1[x].is_empty() {
+            for y in 0..self.board.h {
+                self.set(y, x, Some(false), q, used);
+            }
+            return;
+        }
         let mut l_sum = 0_usize;
         let mut r_sum = self.hints.1[x].iter().sum::<usize>();
         let n = self.hints.1[x].len();
@@ -204,7 +761,7 @@ impl Solver {
             r_sum -= hint;
             let l0 = l_sum + i;
             let r0 = l0 + hint - 1;
-            let r1 = self.board.w - 1 - (r_sum + (n - 1 - i));
+            let r1 = self.board.h - 1 - (r_sum + (n - 1 - i));
             let l1 = r1 - (hint - 1);
             if l1 > r0 {
                 continue;
@@ -331,7 +888,7 @@ impl Solver {
 
         // 後ろから見て確定するマスを埋めていく
         let mut x = self.board.w - 1;
-        let mut i = self.hints.0[y].len() - 1;
+        let mut i = self.hints.0[y].len().wrapping_sub(1);
         while x < self.board.w && i < self.hints.0[y].len() {
             let mut nx = x;
             while nx < self.board.w
@@ -510,7 +1067,7 @@ impl Solver {
 
         // 後ろから見て確定するマスを埋めていく
         let mut y = self.board.h - 1;
-        let mut i = self.hints.1[x].len() - 1;
+        let mut i = self.hints.1[x].len().wrapping_sub(1);
         while y < self.board.h && i < self.hints.1[x].len() {
             let mut ny = y;
             while ny < self.board.h
@@ -641,6 +1198,47 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_solve_empty_lines() {
+        // 手がかりのない列
+        let mut solver = Solver::new(3, 3, (vec![vec![1, 1]; 3], vec![vec![3], vec![], vec![3]]));
+        assert!(solver.solve());
+        assert_eq!(solver.board.get_plain(), [true, false, true].repeat(3));
+        // 手がかりがまったくなくても、最初にすべてバツにして解ける
+        let mut solver = Solver::new(2, 2, (vec![vec![]; 2], vec![vec![]; 2]));
+        assert!(solver.solve());
+        assert_eq!(solver.board.get_plain(), vec![false; 4]);
+    }
+
+    #[test]
+    fn test_solve_non_square() {
+        // 縦長の盤面で、列の長さを高さで数える
+        let mut solver = Solver::new(
+            3,
+            2,
+            (vec![vec![1], vec![1], vec![2]], vec![vec![3], vec![1]]),
+        );
+        assert!(solver.solve());
+        assert_eq!(
+            solver.board.get_plain(),
+            vec![true, false, true, false, true, true]
+        );
+        // 横長の盤面
+        let mut solver = Solver::new(
+            2,
+            4,
+            (
+                vec![vec![4], vec![1]],
+                vec![vec![2], vec![1], vec![1], vec![1]],
+            ),
+        );
+        assert!(solver.solve());
+        assert_eq!(
+            solver.board.get_plain(),
+            vec![true, true, true, true, true, false, false, false]
+        );
+    }
+
     #[test]
     fn test_solve_random() {
         use rand::prelude::*;
@@ -657,4 +1255,142 @@ mod test {
         let mut solver = Solver::new(h, w, hints);
         solver.solve();
     }
+
+    #[test]
+    fn test_gen_symmetry() {
+        let n = 10;
+        for symmetry in [
+            Symmetry::Horizontal,
+            Symmetry::Vertical,
+            Symmetry::Rotational,
+            Symmetry::Diagonal,
+            Symmetry::Quarter,
+        ] {
+            let options = GenOptions {
+                symmetry,
+                ..Default::default()
+            };
+            let (grid, _) = gen_inner(n, n, 0, &options).unwrap();
+            for y in 0..n {
+                for x in 0..n {
+                    let (ry, rx) = symmetry.representative(n, n, y, x);
+                    assert_eq!(grid[y * n + x], grid[ry * n + rx]);
+                }
+            }
+            assert_eq!(grid, gen_inner(n, n, 0, &options).unwrap().0);
+        }
+    }
+
+    #[test]
+    fn test_gen_constraints() {
+        let n = 20;
+        let constraints = Constraints {
+            max_clues_per_line: Some(4),
+            max_run: Some(8),
+            min_run: Some(2),
+            max_single_fraction: Some(0.0),
+        };
+        let options = GenOptions {
+            constraints,
+            ..Default::default()
+        };
+        let (grid, hints) = gen_inner(n, n, 0, &options).unwrap();
+        assert!(constraints.satisfied(&hints));
+        let mut solver = Solver::new(n, n, hints);
+        assert!(solver.solve());
+        assert_eq!(solver.board.get_plain(), grid);
+
+        let conflicting = |constraints| {
+            let options = GenOptions {
+                constraints,
+                ..Default::default()
+            };
+            gen_inner(n, n, 0, &options).unwrap_err()
+        };
+        assert!(matches!(
+            conflicting(Constraints {
+                min_run: Some(5),
+                max_run: Some(4),
+                ..Default::default()
+            }),
+            GenError::Conflicting(_)
+        ));
+        assert!(matches!(
+            conflicting(Constraints {
+                min_run: Some(n),
+                ..Default::default()
+            }),
+            GenError::Conflicting(_)
+        ));
+        assert_eq!(
+            conflicting(Constraints {
+                max_single_fraction: Some(f64::NAN),
+                ..Default::default()
+            }),
+            GenError::Conflicting("max_single_fraction is not a number")
+        );
+    }
+
+    #[test]
+    fn test_gen_symmetry_not_square() {
+        let options = |symmetry| GenOptions {
+            symmetry,
+            ..Default::default()
+        };
+        assert_eq!(
+            gen_inner(6, 8, 0, &options(Symmetry::Diagonal)),
+            Err(GenError::NotSquare)
+        );
+        assert!(gen_inner(6, 8, 0, &options(Symmetry::Quarter)).is_ok());
+    }
+
+    #[test]
+    fn test_gen_smoothness_limit() {
+        let options = |min_smoothness| GenOptions {
+            pattern: Pattern::Blob,
+            min_smoothness,
+            ..Default::default()
+        };
+        for min_smoothness in [1.5, -0.1, f64::NAN] {
+            assert!(matches!(
+                gen_inner(5, 5, 0, &options(min_smoothness)),
+                Err(GenError::Conflicting(_))
+            ));
+        }
+        // 全部塗る列も空の列もない盤面は、すべてのマスが隣と同じ色にはならない
+        assert_eq!(gen_inner(5, 5, 0, &options(1.0)), Err(GenError::Exhausted));
+    }
+
+    #[test]
+    fn test_gen_pattern() {
+        let n = 15;
+        for pattern in [Pattern::Noise, Pattern::Automaton, Pattern::Blob] {
+            let options = GenOptions {
+                pattern,
+                min_smoothness: 0.7,
+                ..Default::default()
+            };
+            let (grid, _) = gen_inner(n, n, 0, &options).unwrap();
+            let grid = grid
+                .chunks(n)
+                .map(|row| row.iter().map(|&f| Some(f)).collect_vec())
+                .collect_vec();
+            assert!(smoothness(&grid) >= 0.7);
+        }
+    }
+
+    #[test]
+    fn test_puzzle_givens() {
+        // 市松模様は手がかりだけでは決まらない
+        let n = 4;
+        let grid = (0..n)
+            .map(|y| (0..n).map(|x| (y + x) % 2 == 0).collect_vec())
+            .collect_vec();
+        assert!(Puzzle::from_grid(&grid, false).givens.is_empty());
+        let puzzle = Puzzle::from_grid(&grid, true);
+        assert!(!puzzle.givens.is_empty());
+        let mut solver = Solver::with_givens(n, n, puzzle.hints, &puzzle.givens);
+        assert!(solver.solve());
+        assert_eq!(solver.board.get_plain(), grid.concat());
+    }
 }
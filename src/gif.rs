@@ -0,0 +1,287 @@
+use crate::raster::{Canvas, Rgb};
+use weezl::{encode::Encoder, BitOrder, LzwError};
+
+/// 色表を使う GIF。`buffer` は常に trailer で終わり、そのまま GIF として読める。
+/// 2 コマ目からは前のコマと変わった範囲だけを書き、変わっていない画素は透明にする
+#[derive(Clone)]
+pub struct Gif {
+    width: u16,
+    height: u16,
+    palette: Vec<Rgb>,
+    /// 色表の大きさは 2^(bits)
+    bits: u8,
+    /// 色表に空きがあれば、その最初の番号を透明色にする
+    transparent: Option<u8>,
+    /// 直前のコマまでを重ねた画像
+    previous: Option<Vec<u8>>,
+    pub buffer: Vec<u8>,
+}
+
+fn le(v: u16) -> [u8; 2] {
+    [(v % (1 << 8)) as u8, (v >> 8) as u8]
+}
+
+impl Gif {
+    /// `palette` は 256 色まで。`loop_count` は再生する回数で、`None` なら繰り返し続ける
+    pub fn new(width: u16, height: u16, palette: &[Rgb], loop_count: Option<u16>) -> Self {
+        assert!(!palette.is_empty() && palette.len() <= 256);
+        let transparent = (palette.len() < 256).then_some(palette.len() as u8);
+        let colors = palette.len() + transparent.is_some() as usize;
+        let bits = (1..=8).find(|&b| colors <= 1 << b).unwrap();
+
+        let mut buffer = Vec::<u8>::new();
+        buffer.extend_from_slice(b"GIF89a");
+        buffer.extend_from_slice(&le(width));
+        buffer.extend_from_slice(&le(height));
+        // 大域色表あり、色の深さ、大きさ
+        buffer.extend_from_slice(&[0x80 | (bits - 1) << 4 | (bits - 1), 0x00, 0x00]);
+        for i in 0..1 << bits {
+            buffer.extend_from_slice(&palette.get(i).copied().unwrap_or([0, 0, 0]));
+        }
+
+        // NETSCAPE の拡張に書くのは最初の 1 回のあとに繰り返す回数 (0 なら無限)。
+        // 1 回だけなら拡張を書かない
+        let repeat = match loop_count {
+            None => Some(0),
+            Some(n) => Some(n.saturating_sub(1)).filter(|&n| n > 0),
+        };
+        if let Some(repeat) = repeat {
+            let [repeat_lower, repeat_upper] = le(repeat);
+            buffer.extend_from_slice(&[0x21, 0xff, 0x0b]);
+            buffer.extend_from_slice(b"NETSCAPE2.0");
+            buffer.extend_from_slice(&[0x03, 0x01, repeat_lower, repeat_upper, 0x00]);
+        }
+        buffer.push(0x3b);
+        Self {
+            width,
+            height,
+            palette: palette.to_vec(),
+            bits,
+            transparent,
+            previous: None,
+            buffer,
+        }
+    }
+
+    /// 色表の番号。色表にない色は最も近い色にする
+    pub fn index(&self, color: Rgb) -> u8 {
+        let dist = |c: &Rgb| {
+            c.iter()
+                .zip(&color)
+                .map(|(&a, &b)| (a as i32 - b as i32).pow(2))
+                .sum::<i32>()
+        };
+        (0..self.palette.len())
+            .min_by_key(|&i| dist(&self.palette[i]))
+            .unwrap() as u8
+    }
+
+    /// 画像全体と同じ大きさの `canvas` を 1 コマ足す。`delay` は 1/100 秒単位
+    pub fn add_canvas(&mut self, canvas: &Canvas, delay: u16) -> Result<(), LzwError> {
+        assert_eq!(
+            (canvas.width, canvas.height),
+            (self.width as usize, self.height as usize)
+        );
+        // 同じ色が続くことが多いので、直前の色を覚えておく
+        let mut last = None;
+        let data = canvas
+            .pixels
+            .iter()
+            .map(|&color| match last {
+                Some((c, i)) if c == color => i,
+                _ => {
+                    let i = self.index(color);
+                    last = Some((color, i));
+                    i
+                }
+            })
+            .collect::<Vec<_>>();
+        self.add(&data, delay)
+    }
+
+    /// 前のコマと変わった画素を囲む (x, y, 幅, 高さ) と、その範囲の画素。
+    /// 変わっていない画素は透明にする
+    fn diff(&self, data: &[u8]) -> ((u16, u16, u16, u16), Vec<u8>) {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let previous = match &self.previous {
+            Some(previous) => previous,
+            None => return ((0, 0, self.width, self.height), data.to_vec()),
+        };
+        let changed = (0..width * height).filter(|&i| previous[i] != data[i]);
+        let (mut x0, mut y0, mut x1, mut y1) = (width, height, 0, 0);
+        for i in changed {
+            let (x, y) = (i % width, i / width);
+            x0 = x0.min(x);
+            y0 = y0.min(y);
+            x1 = x1.max(x + 1);
+            y1 = y1.max(y + 1);
+        }
+        if x0 >= x1 {
+            // 何も変わっていなくても、待ち時間のために 1 画素だけ書く
+            return ((0, 0, 1, 1), vec![self.transparent.unwrap_or(data[0])]);
+        }
+        let sub = (y0..y1)
+            .flat_map(|y| (x0..x1).map(move |x| y * width + x))
+            .map(|i| match self.transparent {
+                Some(transparent) if previous[i] == data[i] => transparent,
+                _ => data[i],
+            })
+            .collect();
+        let rect = (x0 as u16, y0 as u16, (x1 - x0) as u16, (y1 - y0) as u16);
+        (rect, sub)
+    }
+
+    /// 色表の番号の列を 1 コマ足す
+    pub fn add(&mut self, data: &[u8], delay: u16) -> Result<(), LzwError> {
+        let ((x, y, width, height), sub) = self.diff(data);
+        // 最初のコマは画像全体なので透明色はいらない
+        let transparent = self.transparent.filter(|_| self.previous.is_some());
+        self.previous = Some(data.to_vec());
+        let trailer = self.buffer.pop().unwrap();
+
+        let [delay_lower, delay_upper] = le(delay);
+        // 前のコマは消さずに残す (disposal method 1)
+        let graphic_control_extention = [
+            0x21,
+            0xf9,
+            0x04,
+            0x04 | transparent.is_some() as u8,
+            delay_lower,
+            delay_upper,
+            transparent.unwrap_or(0),
+            0x00,
+        ];
+        self.buffer.extend_from_slice(&graphic_control_extention);
+
+        let mut image_block = vec![0x2c];
+        image_block.extend_from_slice(&le(x));
+        image_block.extend_from_slice(&le(y));
+        image_block.extend_from_slice(&le(width));
+        image_block.extend_from_slice(&le(height));
+        // LZW の最小の符号長は 2 以上
+        let min_code_size = self.bits.max(2);
+        image_block.extend_from_slice(&[0x00, min_code_size]);
+        let buffer = Encoder::new(BitOrder::Lsb, min_code_size).encode(&sub)?;
+        let max_size = 0xff;
+        for l in (0..buffer.len()).step_by(max_size) {
+            let r = (l + max_size).min(buffer.len());
+            let block_size = (r - l) as u8;
+            image_block.push(block_size);
+            image_block.extend_from_slice(&buffer[l..r]);
+        }
+        image_block.push(0x00);
+        self.buffer.append(&mut image_block);
+        self.buffer.push(trailer);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// コマごとの (x, y, 幅, 高さ, 色の番号)
+    fn decode(gif: &[u8]) -> Vec<(u16, u16, u16, u16, Vec<u8>)> {
+        let u16_at = |i: usize| gif[i] as u16 | (gif[i + 1] as u16) << 8;
+        let mut i = 13 + 3 * (1 << ((gif[10] & 0x07) + 1));
+        let mut frames = vec![];
+        // サブブロックをつなげたものと、その次の位置
+        let sub_blocks = |mut i: usize| {
+            let mut data = vec![];
+            while gif[i] != 0 {
+                data.extend_from_slice(&gif[i + 1..i + 1 + gif[i] as usize]);
+                i += 1 + gif[i] as usize;
+            }
+            (data, i + 1)
+        };
+        loop {
+            match gif[i] {
+                0x21 => i = sub_blocks(i + 2).1,
+                0x2c => {
+                    let (x, y, w, h) = (u16_at(i + 1), u16_at(i + 3), u16_at(i + 5), u16_at(i + 7));
+                    let min_code_size = gif[i + 10];
+                    let (data, next) = sub_blocks(i + 11);
+                    let pixels = weezl::decode::Decoder::new(BitOrder::Lsb, min_code_size)
+                        .decode(&data)
+                        .unwrap();
+                    assert_eq!(pixels.len(), w as usize * h as usize);
+                    frames.push((x, y, w, h, pixels));
+                    i = next;
+                }
+                0x3b => return frames,
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_gif() {
+        let palette = [[255, 255, 255], [0, 0, 0], [255, 0, 0]];
+        let mut gif = Gif::new(2, 1, &palette, None);
+        // 3 色なので 4 色の色表
+        assert_eq!(gif.buffer[10], 0x80 | 0x10 | 0x01);
+        assert_eq!(gif.buffer.len(), 13 + 4 * 3 + 19 + 1);
+        assert_eq!(gif.index([250, 10, 10]), 2);
+
+        let mut canvas = Canvas::new(2, 1, [255, 255, 255]);
+        canvas.fill_rect(1, 0, 1, 1, [0, 0, 0]);
+        gif.add_canvas(&canvas, 20).unwrap();
+        assert_eq!(decode(&gif.buffer), vec![(0, 0, 2, 1, vec![0, 1])]);
+        assert!(gif.buffer.starts_with(b"GIF89a"));
+        assert_eq!(gif.buffer.last(), Some(&0x3b));
+        // 大きい色表でも書ける
+        let palette = (0..=255).map(|i| [i, i, i]).collect::<Vec<_>>();
+        let mut gif = Gif::new(1, 1, &palette, None);
+        assert_eq!(gif.buffer[10], 0xf7);
+        gif.add(&[200], 10).unwrap();
+        assert_eq!(decode(&gif.buffer)[0].4, vec![200]);
+    }
+
+    #[test]
+    fn test_gif_diff() {
+        let palette = [[255, 255, 255], [0, 0, 0]];
+        let mut gif = Gif::new(4, 3, &palette, None);
+        // 2 色と透明色で 4 色の色表
+        assert_eq!(gif.buffer[10], 0x80 | 0x10 | 0x01);
+        gif.add(&[0; 12], 10).unwrap();
+        let mut data = [0; 12];
+        data[4 + 1] = 1;
+        data[2 * 4 + 2] = 1;
+        gif.add(&data, 10).unwrap();
+        gif.add(&data, 10).unwrap();
+        let frames = decode(&gif.buffer);
+        assert_eq!(frames[0], (0, 0, 4, 3, vec![0; 12]));
+        // 変わった範囲だけを書き、変わっていない画素は透明 (2)
+        assert_eq!(frames[1], (1, 1, 2, 2, vec![1, 2, 2, 1]));
+        assert_eq!(frames[2], (0, 0, 1, 1, vec![2]));
+        // 透明色を使うのは 2 コマ目から
+        let gce = |i: usize| {
+            let p = gif
+                .buffer
+                .windows(3)
+                .enumerate()
+                .filter(|(_, b)| b == &[0x21, 0xf9, 0x04])
+                .nth(i)
+                .unwrap()
+                .0;
+            (gif.buffer[p + 3], gif.buffer[p + 6])
+        };
+        assert_eq!(gce(0), (0x04, 0));
+        assert_eq!(gce(1), (0x05, 2));
+    }
+
+    #[test]
+    fn test_gif_loop_count() {
+        let palette = [[0, 0, 0]];
+        let netscape = |loop_count| {
+            let gif = Gif::new(1, 1, &palette, loop_count);
+            gif.buffer
+                .windows(19)
+                .find(|b| b.starts_with(&[0x21, 0xff, 0x0b]))
+                .map(|b| b[16] as u16 | (b[17] as u16) << 8)
+        };
+        assert_eq!(netscape(None), Some(0));
+        assert_eq!(netscape(Some(1)), None);
+        assert_eq!(netscape(Some(3)), Some(2));
+    }
+}
@@ -0,0 +1,364 @@
+use crate::gen::{Puzzle, Solver};
+use itertools::Itertools;
+use std::fmt;
+
+/// グレースケール画像。画素は 0 (黒) ~ 255 (白)
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<f64>,
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    UnknownFormat,
+    InvalidHeader,
+    Truncated,
+    InvalidSize,
+    Png(png::DecodingError),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnknownFormat => write!(f, "unknown image format"),
+            DecodeError::InvalidHeader => write!(f, "invalid header"),
+            DecodeError::Truncated => write!(f, "image data is truncated"),
+            DecodeError::InvalidSize => write!(f, "invalid image size"),
+            DecodeError::Png(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<png::DecodingError> for DecodeError {
+    fn from(err: png::DecodingError) -> Self {
+        DecodeError::Png(err)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Threshold {
+    /// 大津の方法で閾値を決める
+    Otsu,
+    /// この値より暗い画素を塗る
+    Fixed(u8),
+}
+
+pub struct ImagePuzzle {
+    pub grid: Vec<Vec<bool>>,
+    pub puzzle: Puzzle,
+    /// 手がかりだけでは決まらないマス
+    pub ambiguous: Vec<(usize, usize)>,
+}
+
+fn luminance(r: u8, g: u8, b: u8, a: u8) -> f64 {
+    let l = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+    // 透明な部分は白地に合成する
+    let a = a as f64 / 255.0;
+    l * a + 255.0 * (1.0 - a)
+}
+
+impl Image {
+    /// 幅や高さが 0 のときやバッファの長さが合わないときはエラー
+    pub fn from_rgba(width: usize, height: usize, rgba: &[u8]) -> Result<Self, DecodeError> {
+        let len = width
+            .checked_mul(height)
+            .and_then(|n| n.checked_mul(4))
+            .filter(|&n| n > 0);
+        if len != Some(rgba.len()) {
+            return Err(DecodeError::InvalidSize);
+        }
+        let pixels = rgba
+            .chunks(4)
+            .map(|p| luminance(p[0], p[1], p[2], p[3]))
+            .collect();
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// PNG / PBM (P1, P4) / PGM (P2, P5) を読む
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        match bytes {
+            [0x89, b'P', b'N', b'G', ..] => Self::decode_png(bytes),
+            [b'P', b'1' | b'2' | b'4' | b'5', ..] => Self::decode_pnm(bytes),
+            _ => Err(DecodeError::UnknownFormat),
+        }
+    }
+
+    fn decode_png(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        let buffer = &buffer[..info.buffer_size()];
+        let pixels = match info.color_type {
+            png::ColorType::Grayscale => buffer.iter().map(|&l| l as f64).collect(),
+            png::ColorType::GrayscaleAlpha => buffer
+                .chunks(2)
+                .map(|p| luminance(p[0], p[0], p[0], p[1]))
+                .collect(),
+            png::ColorType::Rgb => buffer
+                .chunks(3)
+                .map(|p| luminance(p[0], p[1], p[2], 255))
+                .collect(),
+            png::ColorType::Rgba => buffer
+                .chunks(4)
+                .map(|p| luminance(p[0], p[1], p[2], p[3]))
+                .collect(),
+            png::ColorType::Indexed => unreachable!(),
+        };
+        Ok(Self {
+            width: info.width as usize,
+            height: info.height as usize,
+            pixels,
+        })
+    }
+
+    fn decode_pnm(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let magic = bytes[1];
+        let mut pos = 2;
+        // ヘッダの数値を 1 つ読む（# から行末まではコメント）
+        let next_number = |pos: &mut usize| -> Result<usize, DecodeError> {
+            loop {
+                match bytes.get(*pos) {
+                    Some(b'#') => {
+                        while bytes.get(*pos).is_some_and(|&c| c != b'\n') {
+                            *pos += 1;
+                        }
+                    }
+                    Some(c) if c.is_ascii_whitespace() => *pos += 1,
+                    Some(_) => break,
+                    None => return Err(DecodeError::Truncated),
+                }
+            }
+            let start = *pos;
+            while bytes.get(*pos).is_some_and(|c| c.is_ascii_digit()) {
+                *pos += 1;
+            }
+            std::str::from_utf8(&bytes[start..*pos])
+                .unwrap()
+                .parse()
+                .map_err(|_| DecodeError::InvalidHeader)
+        };
+        let width = next_number(&mut pos)?;
+        let height = next_number(&mut pos)?;
+        // 0 だと縮小のときに 0 で割ってしまう
+        let size = width
+            .checked_mul(height)
+            .filter(|&size| size > 0)
+            .ok_or(DecodeError::InvalidSize)?;
+        let max = if magic == b'1' || magic == b'4' {
+            1
+        } else {
+            next_number(&mut pos)?
+        };
+        if max == 0 || max > 0xffff {
+            return Err(DecodeError::InvalidHeader);
+        }
+        let scale = |v: usize| v as f64 * 255.0 / max as f64;
+
+        let pixels = match magic {
+            b'1' => bytes[pos..]
+                .iter()
+                .filter(|c| !c.is_ascii_whitespace())
+                .take(size)
+                .map(|&c| if c == b'1' { 0.0 } else { 255.0 })
+                .collect_vec(),
+            b'2' => (0..size)
+                .map(|_| next_number(&mut pos).map(scale))
+                .collect::<Result<Vec<_>, _>>()?,
+            b'4' => {
+                // ヘッダの後の空白 1 文字を飛ばす
+                let data = bytes.get(pos + 1..).ok_or(DecodeError::Truncated)?;
+                let stride = width.div_ceil(8);
+                if stride.checked_mul(height).is_none_or(|n| data.len() < n) {
+                    return Err(DecodeError::Truncated);
+                }
+                (0..height)
+                    .flat_map(|y| {
+                        (0..width).map(move |x| {
+                            if data[y * stride + x / 8] >> (7 - x % 8) & 1 == 1 {
+                                0.0
+                            } else {
+                                255.0
+                            }
+                        })
+                    })
+                    .collect_vec()
+            }
+            b'5' => {
+                let data = bytes.get(pos + 1..).ok_or(DecodeError::Truncated)?;
+                let depth = if max > 0xff { 2 } else { 1 };
+                if data.len() < size.saturating_mul(depth) {
+                    return Err(DecodeError::Truncated);
+                }
+                data.chunks(depth)
+                    .take(size)
+                    .map(|v| scale(v.iter().fold(0, |acc, &b| acc << 8 | b as usize)))
+                    .collect_vec()
+            }
+            _ => unreachable!(),
+        };
+        if pixels.len() < size {
+            return Err(DecodeError::Truncated);
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// 面積平均で h x w に縮小する
+    pub fn downsample(&self, h: usize, w: usize) -> Vec<Vec<f64>> {
+        let range = |i: usize, n: usize, size: usize| {
+            let l = i * size / n;
+            let r = ((i + 1) * size / n).max(l + 1).min(size);
+            l..r
+        };
+        (0..h)
+            .map(|y| {
+                (0..w)
+                    .map(|x| {
+                        let (ys, xs) = (range(y, h, self.height), range(x, w, self.width));
+                        let count = ys.len() * xs.len();
+                        ys.cartesian_product(xs)
+                            .map(|(sy, sx)| self.pixels[sy * self.width + sx])
+                            .sum::<f64>()
+                            / count as f64
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// 大津の方法。クラス間分散が最大になる閾値を返す
+pub fn otsu(values: &[f64]) -> u8 {
+    let mut histogram = [0_usize; 256];
+    for &v in values {
+        histogram[v.round().clamp(0.0, 255.0) as usize] += 1;
+    }
+    let total = values.len() as f64;
+    let sum = histogram
+        .iter()
+        .enumerate()
+        .map(|(i, &count)| i as f64 * count as f64)
+        .sum::<f64>();
+    let (mut weight, mut sum_below) = (0.0, 0.0);
+    let mut best = (0.0, 0);
+    for (t, &count) in histogram.iter().enumerate() {
+        weight += count as f64;
+        sum_below += t as f64 * count as f64;
+        if weight == 0.0 || weight == total {
+            continue;
+        }
+        let mean_below = sum_below / weight;
+        let mean_above = (sum - sum_below) / (total - weight);
+        let variance = weight * (total - weight) * (mean_below - mean_above).powi(2);
+        if variance > best.0 {
+            best = (variance, t + 1);
+        }
+    }
+    best.1.min(255) as u8
+}
+
+/// 閾値より暗いマスを塗る。`dither` なら Floyd–Steinberg で誤差を拡散する
+pub fn binarize(values: &[Vec<f64>], threshold: Threshold, dither: bool) -> Vec<Vec<bool>> {
+    let (h, w) = (values.len(), values[0].len());
+    let threshold = match threshold {
+        Threshold::Otsu => otsu(&values.concat()),
+        Threshold::Fixed(t) => t,
+    } as f64;
+    let mut values = values.to_vec();
+    let mut grid = vec![vec![false; w]; h];
+    for y in 0..h {
+        for x in 0..w {
+            grid[y][x] = values[y][x] < threshold;
+            if !dither {
+                continue;
+            }
+            let error = values[y][x] - if grid[y][x] { 0.0 } else { 255.0 };
+            for (dy, dx, weight) in [(0, 1, 7.0), (1, !0, 3.0), (1, 0, 5.0), (1, 1, 1.0)] {
+                let (ny, nx) = (y + dy, x.wrapping_add(dx));
+                if ny < h && nx < w {
+                    values[ny][nx] += error * weight / 16.0;
+                }
+            }
+        }
+    }
+    grid
+}
+
+pub fn image_puzzle_inner(
+    image: &Image,
+    h: usize,
+    w: usize,
+    threshold: Threshold,
+    dither: bool,
+    with_givens: bool,
+) -> ImagePuzzle {
+    let grid = binarize(&image.downsample(h, w), threshold, dither);
+    let puzzle = Puzzle::from_grid(&grid, with_givens);
+    let mut solver = Solver::new(h, w, puzzle.hints.clone());
+    solver.solve();
+    let ambiguous = (0..h)
+        .cartesian_product(0..w)
+        .filter(|&(y, x)| solver.board.grid[y][x].is_none())
+        .collect();
+    ImagePuzzle {
+        grid,
+        puzzle,
+        ambiguous,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_pnm() {
+        let pbm = Image::decode(b"P1\n# comment\n3 2\n1 0 1\n0 1 0\n").unwrap();
+        assert_eq!((pbm.width, pbm.height), (3, 2));
+        assert_eq!(pbm.pixels, vec![0.0, 255.0, 0.0, 255.0, 0.0, 255.0]);
+
+        let pbm = Image::decode(b"P4\n3 2\n\xa0\x40").unwrap();
+        assert_eq!(pbm.pixels, vec![0.0, 255.0, 0.0, 255.0, 0.0, 255.0]);
+
+        let pgm = Image::decode(b"P5\n2 1\n15\n\x00\x0f").unwrap();
+        assert_eq!(pgm.pixels, vec![0.0, 255.0]);
+
+        assert!(Image::decode(b"P2\n2 2\n255\n0 1 2").is_err());
+        assert!(Image::decode(b"GIF89a").is_err());
+        assert!(Image::decode(b"P1\n0 2\n").is_err());
+        assert!(Image::decode(b"P5\n3 0\n255\n").is_err());
+    }
+
+    #[test]
+    fn test_image_puzzle() {
+        // 白地に黒い正方形
+        let (width, height) = (40, 40);
+        let rgba = (0..height)
+            .cartesian_product(0..width)
+            .flat_map(|(y, x)| {
+                let v = if (10..30).contains(&y) && (8..32).contains(&x) {
+                    0
+                } else {
+                    255
+                };
+                [v, v, v, 255]
+            })
+            .collect_vec();
+        assert!(Image::from_rgba(width, height + 1, &rgba).is_err());
+        assert!(Image::from_rgba(0, 0, &[]).is_err());
+        let image = Image::from_rgba(width, height, &rgba).unwrap();
+        let puzzle = image_puzzle_inner(&image, 10, 10, Threshold::Otsu, false, false);
+        assert_eq!(puzzle.puzzle.hints.0[5], vec![6]);
+        assert!(puzzle.ambiguous.is_empty());
+    }
+}
@@ -0,0 +1,143 @@
+use crate::vis::RenderOptions;
+
+/// 数字 1 文字の幅 (フォントサイズに対する比)。等幅でないフォントでもおおよそこのくらい
+const DIGIT_WIDTH: f64 = 0.6;
+/// 手がかりの数字がマスの幅に占める割合の上限
+const CLUE_FILL: f64 = 0.7;
+
+/// 手がかり 1 つの中心とフォントサイズ
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClueBox {
+    pub x: f64,
+    pub y: f64,
+    pub font_size: f64,
+}
+
+/// 盤面と手がかりの配置。盤面の左上のマスが (origin_x, origin_y) に来る
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layout {
+    pub origin_x: i32,
+    pub origin_y: i32,
+    pub cell_size: i32,
+    /// SVG 全体の大きさ
+    pub width: i32,
+    pub height: i32,
+    pub rows: Vec<Vec<ClueBox>>,
+    pub columns: Vec<Vec<ClueBox>>,
+}
+
+/// 文字列を描いたときのおおよその幅
+pub fn measure(text: &str, font_size: f64) -> f64 {
+    text.chars().count() as f64 * font_size * DIGIT_WIDTH
+}
+
+impl Layout {
+    /// 盤面の外側の余白
+    pub const MARGIN: i32 = 5;
+
+    pub fn new(
+        h: usize,
+        w: usize,
+        hints: &(Vec<Vec<i32>>, Vec<Vec<i32>>),
+        options: &RenderOptions,
+    ) -> Self {
+        let d = options.cell_size;
+        let base = options.font_size.unwrap_or(d as f64 * 2.0 / 3.0);
+        // 2 桁以上の数字はマスの幅に収まるように小さくする
+        let font_size = |num: i32| {
+            let text = num.to_string();
+            base.min(d as f64 * CLUE_FILL / measure(&text, 1.0))
+        };
+        // 横の手がかりは幅の広い数字のぶんだけ枠を広げる
+        let slot =
+            |num: i32| (d as f64).max(measure(&num.to_string(), font_size(num)) + d as f64 / 4.0);
+
+        let gutter_x = hints
+            .0
+            .iter()
+            .map(|hints| hints.iter().map(|&num| slot(num)).sum::<f64>())
+            .fold(0.0, f64::max)
+            .ceil() as i32
+            + d / 2;
+        let gutter_y =
+            hints.1.iter().map(|hints| hints.len()).max().unwrap_or(0) as i32 * d + d / 2;
+        let (origin_x, origin_y) = (gutter_x + Self::MARGIN, gutter_y + Self::MARGIN);
+
+        let rows = hints
+            .0
+            .iter()
+            .enumerate()
+            .map(|(y, hints)| {
+                let mut right = origin_x as f64 - d as f64 / 4.0;
+                let mut boxes = hints
+                    .iter()
+                    .rev()
+                    .map(|&num| {
+                        let width = slot(num);
+                        let clue = ClueBox {
+                            x: right - width / 2.0,
+                            y: (origin_y + d * y as i32) as f64 + d as f64 / 2.0,
+                            font_size: font_size(num),
+                        };
+                        right -= width;
+                        clue
+                    })
+                    .collect::<Vec<_>>();
+                boxes.reverse();
+                boxes
+            })
+            .collect();
+        let columns = hints
+            .1
+            .iter()
+            .enumerate()
+            .map(|(x, hints)| {
+                hints
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &num)| ClueBox {
+                        x: (origin_x + d * x as i32) as f64 + d as f64 / 2.0,
+                        y: (origin_y - d * (hints.len() - i) as i32) as f64 + d as f64 / 2.0,
+                        font_size: font_size(num),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            origin_x,
+            origin_y,
+            cell_size: d,
+            width: origin_x + d * w as i32 + Self::MARGIN,
+            height: origin_y + d * h as i32 + Self::MARGIN,
+            rows,
+            columns,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let options = RenderOptions::default();
+        let hints = (vec![vec![1, 12], vec![3]], vec![vec![1], vec![1, 1, 1]]);
+        let layout = Layout::new(2, 2, &hints, &options);
+        assert_eq!(layout.cell_size, 24);
+        assert_eq!(layout.origin_y, 3 * 24 + 12 + Layout::MARGIN);
+        assert_eq!(layout.width, layout.origin_x + 48 + Layout::MARGIN);
+        // 2 桁の数字は小さくする
+        assert!(layout.rows[0][1].font_size < layout.rows[0][0].font_size);
+        assert_eq!(layout.rows[0][0].font_size, 16.0);
+        // 手がかりは盤面の左 / 上に収まる
+        for clue in layout.rows.iter().flatten() {
+            assert!(0.0 < clue.x && clue.x < layout.origin_x as f64);
+        }
+        for clue in layout.columns.iter().flatten() {
+            assert!(0.0 < clue.y && clue.y < layout.origin_y as f64);
+        }
+        assert!(layout.rows[0][0].x < layout.rows[0][1].x);
+    }
+}
@@ -1,38 +1,809 @@
+mod daily;
+mod font;
 mod gen;
+mod gif;
+mod image;
+mod layout;
+mod line;
+mod pdf;
+mod print;
+mod raster;
+mod search;
+mod seed;
+mod shape;
+mod text;
+mod tikz;
 mod vis;
 extern crate console_error_panic_hook;
+use daily::*;
 use gen::*;
+use image::*;
 use itertools::Itertools;
+use layout::*;
+pub use print::{print_entries, print_pdf_inner, print_svg_inner, Paper, PrintOptions};
 use rand::prelude::*;
+use raster::*;
+use search::*;
+pub use search::{Difficulty, Filter, Stats};
+pub use seed::SeedCode;
+use shape::*;
 use std::panic;
+use text::*;
+use tikz::*;
 use vis::*;
 use wasm_bindgen::prelude::*;
 
+/// 手がかりに対する条件。引数を省略した (`undefined` の) 項目は問わない
 #[wasm_bindgen]
-pub fn gen(h: usize, w: usize, seed: u64) -> Vec<i32> {
-    let ret = gen_inner(h, w, seed);
+#[derive(Default)]
+pub struct GenConstraints {
+    constraints: Constraints,
+}
+
+#[wasm_bindgen]
+impl GenConstraints {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        max_clues_per_line: Option<usize>,
+        max_run: Option<usize>,
+        min_run: Option<usize>,
+        max_single_fraction: Option<f64>,
+    ) -> Self {
+        Self {
+            constraints: Constraints {
+                max_clues_per_line,
+                max_run,
+                min_run,
+                max_single_fraction,
+            },
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub fn gen(
+    h: usize,
+    w: usize,
+    seed: u64,
+    symmetry: u8,
+    pattern: u8,
+    min_smoothness: f64,
+    constraints: &GenConstraints,
+) -> Result<Vec<i32>, JsValue> {
+    let options = GenOptions {
+        symmetry: parse_symmetry(symmetry).map_err(|err| JsValue::from_str(&err))?,
+        pattern: parse_pattern(pattern).map_err(|err| JsValue::from_str(&err))?,
+        min_smoothness,
+        constraints: constraints.constraints,
+    };
+    let ret = gen_inner(h, w, seed, &options).map_err(|err| JsValue::from_str(&err.to_string()))?;
+    Ok(ret.0.iter().map(|&f| f as i32).collect())
+}
+
+/// `v1-12345` のようなバージョン付きの seed から作る。数字だけなら v1、それ以外の文字列はハッシュする
+#[wasm_bindgen]
+pub fn gen_code(
+    h: usize,
+    w: usize,
+    code: &str,
+    symmetry: u8,
+    pattern: u8,
+    min_smoothness: f64,
+    constraints: &GenConstraints,
+) -> Result<Vec<i32>, JsValue> {
+    let code = code
+        .parse::<SeedCode>()
+        .map_err(|err| JsValue::from_str(&err.to_string()))?;
+    let options = GenOptions {
+        symmetry: parse_symmetry(symmetry).map_err(|err| JsValue::from_str(&err))?,
+        pattern: parse_pattern(pattern).map_err(|err| JsValue::from_str(&err))?,
+        min_smoothness,
+        constraints: constraints.constraints,
+    };
+    let ret = code
+        .generate(h, w, &options)
+        .map_err(|err| JsValue::from_str(&err.to_string()))?;
+    Ok(ret.0.iter().map(|&f| f as i32).collect())
+}
+
+/// `gen_code` と同じ seed から、条件を付けずに盤面を作る。CLI で使う
+pub fn gen_code_plain(h: usize, w: usize, code: &str) -> Result<Vec<i32>, String> {
+    let code = code.parse::<SeedCode>().map_err(|err| err.to_string())?;
+    let ret = code
+        .generate(h, w, &GenOptions::default())
+        .map_err(|err| err.to_string())?;
+    Ok(ret.0.iter().map(|&f| f as i32).collect())
+}
+
+/// `seeds` の範囲から `filter` に合う seed を探す
+pub fn search_seeds(
+    h: usize,
+    w: usize,
+    symmetry: u8,
+    pattern: u8,
+    min_smoothness: f64,
+    filter: &Filter,
+    seeds: std::ops::Range<u64>,
+) -> Result<Vec<(u64, Stats)>, String> {
+    let options = GenOptions {
+        symmetry: parse_symmetry(symmetry)?,
+        pattern: parse_pattern(pattern)?,
+        min_smoothness,
+        ..Default::default()
+    };
+    Ok(search_inner(h, w, &options, filter, seeds))
+}
+
+#[wasm_bindgen]
+pub struct Daily {
+    date: String,
+    size: usize,
+    difficulty: String,
+    code: String,
+}
+
+#[wasm_bindgen]
+impl Daily {
+    pub fn date(&self) -> String {
+        self.date.clone()
+    }
+    pub fn size(&self) -> usize {
+        self.size
+    }
+    pub fn difficulty(&self) -> String {
+        self.difficulty.clone()
+    }
+    /// `gen_code` に渡す seed
+    pub fn code(&self) -> String {
+        self.code.clone()
+    }
+}
+
+/// `YYYY-MM-DD` の日のデイリーパズル。`size` が 0 なら曜日で決め、それ以外は 5 ~ 30
+#[wasm_bindgen]
+pub fn daily_puzzle(date: &str, size: usize) -> Result<Daily, JsValue> {
+    let date = date
+        .parse::<Date>()
+        .map_err(|err| JsValue::from_str(&err))?;
+    let daily = daily_puzzle_inner(date, if size == 0 { None } else { Some(size) })
+        .map_err(|err| JsValue::from_str(&err))?;
+    Ok(Daily {
+        date: daily.date.to_string(),
+        size: daily.size,
+        difficulty: daily.difficulty.to_string(),
+        code: daily.code.to_string(),
+    })
+}
+
+/// `Date.now()` の UTC での日付
+#[wasm_bindgen]
+pub fn today(now: f64) -> String {
+    Date::from_unix((now / 1000.0).floor() as i64).to_string()
+}
+
+/// 次のデイリーパズルまでの秒数
+#[wasm_bindgen]
+pub fn daily_countdown(now: f64) -> f64 {
+    seconds_until_next((now / 1000.0).floor() as i64) as f64
+}
+
+#[wasm_bindgen]
+pub fn compose(h: usize, w: usize, seed: u64) -> Vec<i32> {
+    let ret = compose_inner(h, w, seed);
     ret.0.iter().map(|&f| f as i32).collect()
 }
 
+#[wasm_bindgen]
+pub struct PuzzleBoard {
+    board: Vec<i32>,
+    hints: Vec<i32>,
+    givens: Vec<i32>,
+    ambiguous: Vec<i32>,
+}
+
+#[wasm_bindgen]
+impl PuzzleBoard {
+    pub fn board(&self) -> Vec<i32> {
+        self.board.clone()
+    }
+    /// `vis_board` に渡せる形式の手がかり
+    pub fn hints(&self) -> Vec<i32> {
+        self.hints.clone()
+    }
+    /// 最初から決まっているマスの添字 (y * w + x)。値は `board` と同じ
+    pub fn givens(&self) -> Vec<i32> {
+        self.givens.clone()
+    }
+    /// 手がかりだけでは決まらないマスの添字 (y * w + x)
+    pub fn ambiguous(&self) -> Vec<i32> {
+        self.ambiguous.clone()
+    }
+}
+
+impl PuzzleBoard {
+    fn new(w: usize, grid: &[Vec<bool>], puzzle: &Puzzle, ambiguous: &[(usize, usize)]) -> Self {
+        Self {
+            board: grid.iter().flatten().map(|&f| f as i32).collect(),
+            hints: encode_hints(&puzzle.hints),
+            givens: puzzle
+                .givens
+                .iter()
+                .map(|&(y, x, _)| (y * w + x) as i32)
+                .collect(),
+            ambiguous: ambiguous.iter().map(|&(y, x)| (y * w + x) as i32).collect(),
+        }
+    }
+}
+
+/// RGBA の画素列から h x w のパズルを作る。`threshold` が負なら大津の方法で決める。
+/// `with_givens` なら手がかりだけで解けないときに最初から決まっているマスを与える
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn image_board(
+    h: usize,
+    w: usize,
+    width: usize,
+    height: usize,
+    rgba: Vec<u8>,
+    threshold: i32,
+    dither: bool,
+    with_givens: bool,
+) -> Result<PuzzleBoard, JsValue> {
+    panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let image = Image::from_rgba(width, height, &rgba)
+        .map_err(|err| JsValue::from_str(&err.to_string()))?;
+    let puzzle = image_puzzle_inner(
+        &image,
+        h,
+        w,
+        parse_threshold(threshold),
+        dither,
+        with_givens,
+    );
+    Ok(PuzzleBoard::new(
+        w,
+        &puzzle.grid,
+        &puzzle.puzzle,
+        &puzzle.ambiguous,
+    ))
+}
+
+/// PNG / PBM / PGM のバイト列から h x w のパズルを作る
+pub fn image_board_from_bytes(
+    h: usize,
+    w: usize,
+    bytes: &[u8],
+    threshold: i32,
+    dither: bool,
+    with_givens: bool,
+) -> Result<PuzzleBoard, String> {
+    let image = Image::decode(bytes).map_err(|err| err.to_string())?;
+    let puzzle = image_puzzle_inner(
+        &image,
+        h,
+        w,
+        parse_threshold(threshold),
+        dither,
+        with_givens,
+    );
+    Ok(PuzzleBoard::new(
+        w,
+        &puzzle.grid,
+        &puzzle.puzzle,
+        &puzzle.ambiguous,
+    ))
+}
+
+/// 文字列が絵になる h x w のパズルを作る。`with_givens` は `image_board` と同じ
+#[wasm_bindgen]
+pub fn text_board(
+    h: usize,
+    w: usize,
+    text: &str,
+    with_givens: bool,
+) -> Result<PuzzleBoard, JsValue> {
+    let puzzle = text_puzzle_inner(h, w, text, with_givens)
+        .map_err(|err| JsValue::from_str(&err.to_string()))?;
+    Ok(PuzzleBoard::new(w, &puzzle.grid, &puzzle.puzzle, &[]))
+}
+
+/// 盤面を手がかりだけで解けるようにするのに必要な、最初から決まっているマスの添字 (y * w + x)。
+/// 手がかりだけで解けない、`compose` などで作った盤面に使う
+#[wasm_bindgen]
+pub fn puzzle_givens(h: usize, w: usize, board: Vec<i32>) -> Result<Vec<i32>, JsValue> {
+    if h == 0 || w == 0 || board.len() != h * w {
+        return Err(JsValue::from_str("board size does not match h x w"));
+    }
+    let grid = board
+        .chunks(w)
+        .map(|row| row.iter().map(|&v| v == 1).collect_vec())
+        .collect_vec();
+    let puzzle = Puzzle::from_grid(&grid, true);
+    Ok(puzzle
+        .givens
+        .iter()
+        .map(|&(y, x, _)| (y * w + x) as i32)
+        .collect())
+}
+
 #[wasm_bindgen]
 pub fn gen_seed() -> String {
-    rand::thread_rng().gen_range(0..1_u64 << 53).to_string()
+    SeedCode::new(rand::thread_rng().gen_range(0..1_u64 << 53)).to_string()
 }
 
+/// 描き方の設定。`new Theme()` がこれまでの見た目で、各項目をプロパティとして変えられる。
+/// 色は `#rrggbb`、`rgb(...)`、CSS の色の名前で、読めない色は無視して前の色のままにする
 #[wasm_bindgen]
-pub fn vis_grid(h: usize, w: usize, d: i32, board: Vec<i32>) -> String {
+#[derive(Default)]
+pub struct Theme {
+    options: RenderOptions,
+}
+
+#[wasm_bindgen]
+impl Theme {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// 組み込みの配色。0: light, 1: dark, 2: high contrast, 3: 1 型・2 型色覚向け
+    pub fn preset(preset: u8) -> Result<Theme, JsValue> {
+        Ok(Self {
+            options: RenderOptions::preset(
+                parse_preset(preset).map_err(|err| JsValue::from_str(&err))?,
+            ),
+        })
+    }
+    #[wasm_bindgen(getter)]
+    pub fn cell_size(&self) -> i32 {
+        self.options.cell_size
+    }
+    /// 1 マスの一辺 (px)。1 ~ 200 に収める
+    #[wasm_bindgen(setter)]
+    pub fn set_cell_size(&mut self, cell_size: i32) {
+        self.options.cell_size = cell_size.clamp(1, 200);
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_background(&mut self, color: String) {
+        set_color(&mut self.options.palette.background, color);
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_fill(&mut self, color: String) {
+        set_color(&mut self.options.palette.fill, color);
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_cross(&mut self, color: String) {
+        set_color(&mut self.options.palette.cross, color);
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_grid(&mut self, color: String) {
+        set_color(&mut self.options.palette.grid, color);
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_frame(&mut self, color: String) {
+        set_color(&mut self.options.palette.frame, color);
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_clue(&mut self, color: String) {
+        set_color(&mut self.options.palette.clue, color);
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_clue_satisfied(&mut self, color: String) {
+        set_color(&mut self.options.palette.clue_satisfied, color);
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_conflict(&mut self, color: String) {
+        set_color(&mut self.options.palette.conflict, color);
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_cursor(&mut self, color: String) {
+        set_color(&mut self.options.palette.cursor, color);
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_given_fill(&mut self, color: String) {
+        set_color(&mut self.options.palette.given_fill, color);
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_given_background(&mut self, color: String) {
+        set_color(&mut self.options.palette.given_background, color);
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_font_family(&mut self, font_family: Option<String>) {
+        self.options.font_family = font_family;
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_font_size(&mut self, font_size: Option<f64>) {
+        self.options.font_size = font_size;
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_grid_width(&mut self, width: f64) {
+        self.options.grid_width = width;
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_cross_width(&mut self, width: f64) {
+        self.options.cross_width = width;
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_frame_width(&mut self, width: f64) {
+        self.options.frame_width = width;
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_cursor_width(&mut self, width: f64) {
+        self.options.cursor_width = width;
+    }
+    /// 太線を引く間隔 (マス)
+    #[wasm_bindgen(setter)]
+    pub fn set_major_interval(&mut self, interval: usize) {
+        self.options.major_interval = interval.max(1);
+    }
+    /// 同じ色のマスをまとめて描き、SVG を小さくする。`vis_board_diff` とは一緒に使えない
+    #[wasm_bindgen(setter)]
+    pub fn set_compact(&mut self, compact: bool) {
+        self.options.compact = compact;
+    }
+    /// 手がかりと矛盾している列を強調する
+    #[wasm_bindgen(setter)]
+    pub fn set_highlight_conflicts(&mut self, highlight: bool) {
+        self.options.highlight_conflicts = highlight;
+    }
+}
+
+#[wasm_bindgen]
+pub fn vis_grid(h: usize, w: usize, board: Vec<i32>, theme: &Theme) -> String {
+    let board = parse_board(h, w, &board);
+    vis_grid_inner(h, w, &board, &theme.options)
+}
+
+/// `vis_grid` と同じ絵の PNG
+#[wasm_bindgen]
+pub fn png_grid(h: usize, w: usize, board: Vec<i32>, theme: &Theme) -> Vec<u8> {
     let board = parse_board(h, w, &board);
-    vis_grid_inner(h, w, d, &board)
+    raster_grid_inner(h, w, &board, &theme.options).to_png()
 }
 
+/// `vis_board` と同じ絵の PNG。`board` をすべて 2 にすれば問題だけの画像になる
 #[wasm_bindgen]
-pub fn vis_gif(h: usize, w: usize, d: u16, boards: Vec<i32>, max_turn: usize) -> Vec<u8> {
+pub fn png_board(
+    h: usize,
+    w: usize,
+    board: Vec<i32>,
+    hints: Vec<i32>,
+    givens: Vec<i32>,
+    theme: &Theme,
+) -> Vec<u8> {
     panic::set_hook(Box::new(console_error_panic_hook::hook));
-    assert_eq!(max_turn * h * w, boards.len());
-    let boards = (0..max_turn)
-        .map(|t| parse_board_unwrap(h, w, &boards[t * h * w..(t + 1) * h * w]))
+    let board = parse_board(h, w, &board);
+    let hints = parse_hints(h, w, &hints);
+    let givens = parse_givens(h, w, &givens);
+    let layout = Layout::new(h, w, &hints, &theme.options);
+    raster_board_inner(h, w, &board, &hints, &givens, &layout, &theme.options).to_png()
+}
+
+/// 空白かカンマで区切った seed の問題を印刷用のページにする。
+/// ページごとの SVG の配列を返す。`paper` は 0: A4, 1: Letter
+#[wasm_bindgen]
+pub fn print_sheets(
+    h: usize,
+    w: usize,
+    codes: &str,
+    paper: u8,
+    per_page: usize,
+    title: String,
+    answers: bool,
+) -> Result<Vec<String>, JsValue> {
+    let entries = print_entries(h, w, codes).map_err(|err| JsValue::from_str(&err))?;
+    let options = PrintOptions {
+        paper: parse_paper(paper).map_err(|err| JsValue::from_str(&err))?,
+        per_page,
+        title,
+        answers,
+    };
+    Ok(print_svg_inner(&entries, &options))
+}
+
+/// `print_sheets` と同じページの PDF。`title` は印字できる ASCII だけ
+#[wasm_bindgen]
+pub fn print_pdf(
+    h: usize,
+    w: usize,
+    codes: &str,
+    paper: u8,
+    per_page: usize,
+    title: String,
+    answers: bool,
+) -> Result<Vec<u8>, JsValue> {
+    let entries = print_entries(h, w, codes).map_err(|err| JsValue::from_str(&err))?;
+    let options = PrintOptions {
+        paper: parse_paper(paper).map_err(|err| JsValue::from_str(&err))?,
+        per_page,
+        title,
+        answers,
+    };
+    print_pdf_inner(&entries, &options).map_err(|err| JsValue::from_str(&err))
+}
+
+/// 問題を TikZ の図にする。`with_solution` なら `board` の塗ったマスも描く。
+/// `cell_size` は 1 マスの一辺 (cm)
+#[wasm_bindgen]
+pub fn tikz(
+    h: usize,
+    w: usize,
+    board: Vec<i32>,
+    hints: Vec<i32>,
+    with_solution: bool,
+    cell_size: f64,
+    theme: &Theme,
+) -> String {
+    let hints = parse_hints(h, w, &hints);
+    let solution = board
+        .chunks(w)
+        .map(|row| row.iter().map(|&f| f == 1).collect_vec())
+        .collect_vec();
+    let options = TikzOptions {
+        cell_size,
+        document: true,
+    };
+    tikz_inner(
+        h,
+        w,
+        &hints,
+        with_solution.then_some(&solution[..]),
+        &options,
+        &theme.options,
+    )
+}
+
+/// GIF の時間の設定。時間は 1/100 秒単位で、引数を省略した (`undefined` の) 項目はこれまでどおり。
+/// `timestamps` (ミリ秒) があればコマの時刻どおりに、`total` があれば全体がその長さになるように流す
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct GifSettings {
+    options: GifOptions,
+}
+
+#[wasm_bindgen]
+impl GifSettings {
+    /// `hold` と `intro` は 0 なら止めない
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        delay: Option<u16>,
+        timestamps: Option<Vec<f64>>,
+        total: Option<u32>,
+        loop_count: Option<u16>,
+        hold: Option<u16>,
+        intro: Option<u16>,
+    ) -> Self {
+        let default = GifOptions::default();
+        let speed = match (timestamps, total) {
+            (Some(timestamps), _) => GifSpeed::RealTime(timestamps),
+            (None, Some(total)) => GifSpeed::Total(total),
+            (None, None) => GifSpeed::Fixed,
+        };
+        Self {
+            options: GifOptions {
+                delay: delay.unwrap_or(default.delay),
+                speed,
+                loop_count,
+                hold: hold.map_or(default.hold, |hold| Some(hold).filter(|&t| t > 0)),
+                intro: intro.filter(|&t| t > 0),
+            },
+        }
+    }
+}
+
+/// `boards` は盤面を `h * w` ずつつないだもの。`cursors` はコマごとのカーソルの位置
+/// `y * w + x` で、負ならカーソルを描かない
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn vis_gif(
+    h: usize,
+    w: usize,
+    boards: Vec<i32>,
+    cursors: Vec<i32>,
+    hints: Vec<i32>,
+    givens: Vec<i32>,
+    theme: &Theme,
+    settings: &GifSettings,
+) -> Vec<u8> {
+    panic::set_hook(Box::new(console_error_panic_hook::hook));
+    assert_eq!(cursors.len() * h * w, boards.len());
+    let frames = boards
+        .chunks(h * w)
+        .zip(cursors)
+        .map(|(board, cursor)| ReplayFrame {
+            board: parse_board(h, w, board),
+            cursor: (cursor >= 0).then(|| (cursor as usize / w, cursor as usize % w)),
+        })
+        .collect_vec();
+    let hints = parse_hints(h, w, &hints);
+    let givens = parse_givens(h, w, &givens);
+    vis_gif_inner(
+        h,
+        w,
+        &frames,
+        &hints,
+        &givens,
+        &theme.options,
+        &settings.options,
+    )
+}
+
+/// プレイ中に 1 コマずつ足していく GIF。盤面をすべて覚えておかなくてよい
+#[wasm_bindgen]
+pub struct GifEncoder {
+    h: usize,
+    w: usize,
+    encoder: ReplayEncoder,
+}
+
+#[wasm_bindgen]
+impl GifEncoder {
+    /// `loop_count` は再生する回数で、省略すると繰り返し続ける
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        h: usize,
+        w: usize,
+        hints: Vec<i32>,
+        givens: Vec<i32>,
+        theme: &Theme,
+        loop_count: Option<u16>,
+    ) -> Self {
+        panic::set_hook(Box::new(console_error_panic_hook::hook));
+        let hints = parse_hints(h, w, &hints);
+        let givens = parse_givens(h, w, &givens);
+        Self {
+            h,
+            w,
+            encoder: ReplayEncoder::new(h, w, hints, givens, theme.options.clone(), loop_count),
+        }
+    }
+    /// `cursor` は `y * w + x` で、負ならカーソルを描かない。`delay` は 1/100 秒単位
+    pub fn push_frame(&mut self, board: Vec<i32>, cursor: i32, delay: u16) {
+        let (h, w) = (self.h, self.w);
+        let frame = ReplayFrame {
+            board: parse_board(h, w, &board),
+            cursor: (cursor >= 0).then(|| (cursor as usize / w, cursor as usize % w)),
+        };
+        self.encoder.push(&frame, delay);
+    }
+    /// GIF のバイト列。これ以降は使えない
+    pub fn finish(self) -> Vec<u8> {
+        self.encoder.finish()
+    }
+    /// 最後に `board` をカーソルなしで足した GIF のバイト列。記録はそのまま続けられる
+    pub fn snapshot(&self, board: Vec<i32>, delay: u16) -> Vec<u8> {
+        let frame = ReplayFrame {
+            board: parse_board(self.h, self.w, &board),
+            cursor: None,
+        };
+        self.encoder.snapshot(&frame, delay)
+    }
+}
+
+/// プレイ中に 1 コマずつ足していく、`vis_animated` と同じ SVG。`GifEncoder` と同じコマを足せば同じ移り変わりになる
+#[wasm_bindgen]
+pub struct AnimatedSvgEncoder {
+    h: usize,
+    w: usize,
+    encoder: AnimatedEncoder,
+}
+
+#[wasm_bindgen]
+impl AnimatedSvgEncoder {
+    #[wasm_bindgen(constructor)]
+    pub fn new(h: usize, w: usize, hints: Vec<i32>, givens: Vec<i32>, theme: &Theme) -> Self {
+        panic::set_hook(Box::new(console_error_panic_hook::hook));
+        let hints = parse_hints(h, w, &hints);
+        let givens = parse_givens(h, w, &givens);
+        Self {
+            h,
+            w,
+            encoder: AnimatedEncoder::new(h, w, hints, givens, &theme.options),
+        }
+    }
+    /// `delay` は 1/100 秒単位
+    pub fn push_frame(&mut self, board: Vec<i32>, delay: u16) {
+        self.encoder
+            .push(&parse_board(self.h, self.w, &board), delay);
+    }
+    /// SVG の文字列。これ以降は使えない
+    pub fn finish(self) -> String {
+        self.encoder.finish()
+    }
+    /// 最後に `board` を足した SVG の文字列。記録はそのまま続けられる
+    pub fn snapshot(&self, board: Vec<i32>) -> String {
+        self.encoder.snapshot(&parse_board(self.h, self.w, &board))
+    }
+}
+
+/// `vis_gif` と同じ盤面の移り変わりを、拡大してもぼやけない SMIL のアニメーション付きの SVG にする。
+/// 一度だけ流れて最後の盤面で止まるので、`settings` の `loop_count` は使わない
+#[wasm_bindgen]
+pub fn vis_animated(
+    h: usize,
+    w: usize,
+    boards: Vec<i32>,
+    hints: Vec<i32>,
+    givens: Vec<i32>,
+    theme: &Theme,
+    settings: &GifSettings,
+) -> String {
+    panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let frames = boards
+        .chunks(h * w)
+        .map(|board| ReplayFrame {
+            board: parse_board(h, w, board),
+            cursor: None,
+        })
         .collect_vec();
-    vis_gif_inner(h, w, d, &boards)
+    let hints = parse_hints(h, w, &hints);
+    let givens = parse_givens(h, w, &givens);
+    vis_animated_inner(
+        h,
+        w,
+        &frames,
+        &hints,
+        &givens,
+        &theme.options,
+        &settings.options,
+    )
+}
+
+/// 盤面と手がかりの配置。盤面の左上のマスが (origin_x, origin_y) に来る
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct BoardLayout {
+    layout: Layout,
+}
+
+#[wasm_bindgen]
+impl BoardLayout {
+    #[wasm_bindgen(getter)]
+    pub fn origin_x(&self) -> i32 {
+        self.layout.origin_x
+    }
+    #[wasm_bindgen(getter)]
+    pub fn origin_y(&self) -> i32 {
+        self.layout.origin_y
+    }
+    #[wasm_bindgen(getter)]
+    pub fn cell_size(&self) -> i32 {
+        self.layout.cell_size
+    }
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> i32 {
+        self.layout.width
+    }
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> i32 {
+        self.layout.height
+    }
+}
+
+#[wasm_bindgen]
+pub struct RenderedBoard {
+    svg: String,
+    layout: BoardLayout,
+}
+
+#[wasm_bindgen]
+impl RenderedBoard {
+    pub fn svg(&self) -> String {
+        self.svg.clone()
+    }
+    /// `vis_cursor` に渡す配置
+    pub fn layout(&self) -> BoardLayout {
+        self.layout.clone()
+    }
+}
+
+/// 盤面 (0: 白, 1: 黒) の手がかりを `vis_board` に渡せる形式で返す
+#[wasm_bindgen]
+pub fn board_hints(h: usize, w: usize, board: Vec<i32>) -> Vec<i32> {
+    let grid = parse_board_unwrap(h, w, &board)
+        .into_iter()
+        .map(|row| row.into_iter().map(Some).collect())
+        .collect();
+    encode_hints(&Board::new(h, w, grid).get_hints())
 }
 
 #[wasm_bindgen]
@@ -41,13 +812,41 @@ pub fn vis_board(
     w: usize,
     board: Vec<i32>,
     hints: Vec<i32>,
-    offset_y: i32,
-    offset_x: i32,
-) -> String {
+    givens: Vec<i32>,
+    theme: &Theme,
+) -> RenderedBoard {
     panic::set_hook(Box::new(console_error_panic_hook::hook));
     let board = parse_board(h, w, &board);
     let hints = parse_hints(h, w, &hints);
-    vis_board_inner(h, w, &board, &hints, "black", offset_y, offset_x)
+    let givens = parse_givens(h, w, &givens);
+    let layout = Layout::new(h, w, &hints, &theme.options);
+    let svg = vis_board_inner(h, w, &board, &hints, &givens, &layout, &theme.options);
+    RenderedBoard {
+        svg,
+        layout: BoardLayout { layout },
+    }
+}
+
+/// `before` から `after` に変わったマスと手がかりの書き換えを JSON の配列で返す。
+/// 属性は `{"op": "set", "id", "name", "value"}`、バツの要素は
+/// `{"op": "add", "id", "parent", "markup"}` と `{"op": "remove", "id"}` で足し引きする
+#[wasm_bindgen]
+pub fn vis_board_diff(
+    h: usize,
+    w: usize,
+    before: Vec<i32>,
+    after: Vec<i32>,
+    hints: Vec<i32>,
+    givens: Vec<i32>,
+    theme: &Theme,
+) -> String {
+    panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let before = parse_board(h, w, &before);
+    let after = parse_board(h, w, &after);
+    let hints = parse_hints(h, w, &hints);
+    let givens = parse_givens(h, w, &givens);
+    let patches = vis_board_diff_inner(h, w, &before, &after, &hints, &givens, &theme.options);
+    format!("[{}]", patches.iter().map(Patch::to_json).join(","))
 }
 
 #[wasm_bindgen]
@@ -56,15 +855,18 @@ pub fn vis_gaming_boards(
     w: usize,
     board: Vec<i32>,
     hints: Vec<i32>,
-    offset_y: i32,
-    offset_x: i32,
+    givens: Vec<i32>,
+    theme: &Theme,
 ) -> String {
     let board = parse_board(h, w, &board);
     let hints = parse_hints(h, w, &hints);
-    vis_gaming_boards_inner(h, w, &board, &hints, offset_y, offset_x)
+    let givens = parse_givens(h, w, &givens);
+    let layout = Layout::new(h, w, &hints, &theme.options);
+    vis_gaming_boards_inner(h, w, &board, &hints, &givens, &layout, &theme.options)
 }
 
 #[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
 pub fn set(
     y: usize,
     x: usize,
@@ -73,10 +875,12 @@ pub fn set(
     w: usize,
     board: Vec<i32>,
     hints: Vec<i32>,
+    givens: Vec<i32>,
 ) -> Vec<i32> {
     let mut board = parse_board(h, w, &board);
     let hints = parse_hints(h, w, &hints);
-    set_inner(y, x, val, &mut board, &hints);
+    let givens = parse_givens(h, w, &givens);
+    set_inner(y, x, val, &mut board, &hints, &givens);
     board
         .iter()
         .flatten()
@@ -89,8 +893,15 @@ pub fn set(
 }
 
 #[wasm_bindgen]
-pub fn vis_cursor(h: usize, w: usize, y: usize, x: usize, offset_y: i32, offset_x: i32) -> String {
-    vis_cursor_inner(h, w, y, x, offset_y, offset_x)
+pub fn vis_cursor(
+    h: usize,
+    w: usize,
+    y: usize,
+    x: usize,
+    layout: &BoardLayout,
+    theme: &Theme,
+) -> String {
+    vis_cursor_inner(h, w, y, x, &layout.layout, &theme.options)
 }
 
 fn parse_board(h: usize, w: usize, board: &[i32]) -> Vec<Vec<Option<bool>>> {
@@ -107,6 +918,64 @@ fn parse_board(h: usize, w: usize, board: &[i32]) -> Vec<Vec<Option<bool>>> {
         })
         .collect::<Vec<_>>()
 }
+fn parse_symmetry(symmetry: u8) -> Result<Symmetry, String> {
+    match symmetry {
+        0 => Ok(Symmetry::None),
+        1 => Ok(Symmetry::Horizontal),
+        2 => Ok(Symmetry::Vertical),
+        3 => Ok(Symmetry::Rotational),
+        4 => Ok(Symmetry::Diagonal),
+        5 => Ok(Symmetry::Quarter),
+        _ => Err(format!("unknown symmetry: {}", symmetry)),
+    }
+}
+fn parse_pattern(pattern: u8) -> Result<Pattern, String> {
+    match pattern {
+        0 => Ok(Pattern::Random),
+        1 => Ok(Pattern::Noise),
+        2 => Ok(Pattern::Automaton),
+        3 => Ok(Pattern::Blob),
+        _ => Err(format!("unknown pattern: {}", pattern)),
+    }
+}
+fn encode_hints(hints: &Hints) -> Vec<i32> {
+    hints
+        .0
+        .iter()
+        .chain(hints.1.iter())
+        .flat_map(|hints| {
+            std::iter::once(hints.len() as i32).chain(hints.iter().map(|&h| h as i32))
+        })
+        .collect()
+}
+fn parse_preset(preset: u8) -> Result<Preset, String> {
+    match preset {
+        0 => Ok(Preset::Light),
+        1 => Ok(Preset::Dark),
+        2 => Ok(Preset::HighContrast),
+        3 => Ok(Preset::ColorBlind),
+        _ => Err(format!("unknown preset: {}", preset)),
+    }
+}
+fn set_color(target: &mut String, color: String) {
+    if parse_color(&color).is_some() {
+        *target = color;
+    }
+}
+fn parse_paper(paper: u8) -> Result<Paper, String> {
+    match paper {
+        0 => Ok(Paper::A4),
+        1 => Ok(Paper::Letter),
+        _ => Err(format!("unknown paper: {}", paper)),
+    }
+}
+fn parse_threshold(threshold: i32) -> Threshold {
+    if threshold < 0 {
+        Threshold::Otsu
+    } else {
+        Threshold::Fixed(threshold.min(255) as u8)
+    }
+}
 fn parse_board_unwrap(h: usize, w: usize, board: &[i32]) -> Vec<Vec<bool>> {
     (0..h)
         .map(|y| {
@@ -120,7 +989,15 @@ fn parse_board_unwrap(h: usize, w: usize, board: &[i32]) -> Vec<Vec<bool>> {
         })
         .collect::<Vec<_>>()
 }
-fn parse_hints(h: usize, w: usize, hints: &Vec<i32>) -> (Vec<Vec<i32>>, Vec<Vec<i32>>) {
+/// 最初から決まっているマスの添字 (y * w + x) の列を盤面の形にする
+fn parse_givens(h: usize, w: usize, givens: &[i32]) -> Vec<Vec<bool>> {
+    let mut ret = vec![vec![false; w]; h];
+    for &i in givens {
+        ret[i as usize / w][i as usize % w] = true;
+    }
+    ret
+}
+fn parse_hints(h: usize, w: usize, hints: &[i32]) -> (Vec<Vec<i32>>, Vec<Vec<i32>>) {
     let mut ret: Vec<Vec<i32>> = vec![];
     let mut i = 0;
     while i < hints.len() {
@@ -0,0 +1,156 @@
+/// 1 列の各ブロックの置ける開始位置。
+/// `line` は `Some(true)` が塗り、`Some(false)` がバツ、`None` が未確定。
+/// どう置いても `line` と矛盾するなら `None`
+pub fn placements(line: &[Option<bool>], clues: &[usize]) -> Option<Vec<Vec<usize>>> {
+    let (n, k) = (line.len(), clues.len());
+    // filled[p] = line[..p] の塗りの個数
+    let mut filled = vec![0; n + 1];
+    for p in 0..n {
+        filled[p + 1] = filled[p] + (line[p] == Some(true)) as usize;
+    }
+    let mut crossed = vec![0; n + 1];
+    for p in 0..n {
+        crossed[p + 1] = crossed[p] + (line[p] == Some(false)) as usize;
+    }
+    let empty = |l: usize, r: usize| filled[r] == filled[l];
+    let fits = |l: usize, r: usize| r <= n && crossed[r] == crossed[l];
+
+    // fwd[i][p] = line[..p] に前から i 個のブロックを置ける
+    let mut fwd = vec![vec![false; n + 1]; k + 1];
+    for (p, f) in fwd[0].iter_mut().enumerate() {
+        *f = empty(0, p);
+    }
+    for i in 1..=k {
+        let len = clues[i - 1];
+        for p in 1..=n {
+            fwd[i][p] = fwd[i][p - 1] && line[p - 1] != Some(true);
+            if !fwd[i][p] && p >= len && fits(p - len, p) {
+                let s = p - len;
+                fwd[i][p] = if s == 0 {
+                    i == 1
+                } else {
+                    line[s - 1] != Some(true) && fwd[i - 1][s - 1]
+                };
+            }
+        }
+    }
+    if !fwd[k][n] {
+        return None;
+    }
+
+    // bwd[i][p] = line[p..] に i 番目以降のブロックを置ける
+    let mut bwd = vec![vec![false; n + 1]; k + 1];
+    for (p, b) in bwd[k].iter_mut().enumerate() {
+        *b = empty(p, n);
+    }
+    for i in (0..k).rev() {
+        let len = clues[i];
+        for p in (0..n).rev() {
+            bwd[i][p] = bwd[i][p + 1] && line[p] != Some(true);
+            if !bwd[i][p] && fits(p, p + len) {
+                let e = p + len;
+                bwd[i][p] = if e == n {
+                    i + 1 == k
+                } else {
+                    line[e] != Some(true) && bwd[i + 1][e + 1]
+                };
+            }
+        }
+    }
+
+    let starts = (0..k)
+        .map(|i| {
+            let len = clues[i];
+            (0..n)
+                .filter(|&s| {
+                    let e = s + len;
+                    let left = if s == 0 {
+                        i == 0
+                    } else {
+                        line[s - 1] != Some(true) && fwd[i][s - 1]
+                    };
+                    let right = if e == n {
+                        i + 1 == k
+                    } else {
+                        e < n && line[e] != Some(true) && bwd[i + 1][e + 1]
+                    };
+                    fits(s, e) && left && right
+                })
+                .collect()
+        })
+        .collect();
+    Some(starts)
+}
+
+/// 手がかりを満たすように残りのマスを塗れるか
+pub fn is_consistent(line: &[Option<bool>], clues: &[usize]) -> bool {
+    placements(line, clues).is_some()
+}
+
+/// 各手がかりの位置が確定して、すべて塗られているか。
+/// 置き方が 1 つもない列では何も満たしていないことにする
+pub fn satisfied(line: &[Option<bool>], clues: &[usize]) -> Vec<bool> {
+    match placements(line, clues) {
+        Some(starts) => starts
+            .iter()
+            .zip(clues)
+            .map(|(starts, &len)| {
+                starts.len() == 1
+                    && line[starts[0]..starts[0] + len]
+                        .iter()
+                        .all(|&c| c == Some(true))
+            })
+            .collect(),
+        None => vec![false; clues.len()],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(line: &str) -> Vec<Option<bool>> {
+        line.chars()
+            .map(|c| match c {
+                '#' => Some(true),
+                'x' => Some(false),
+                '.' => None,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_placements() {
+        assert_eq!(
+            placements(&parse("....."), &[2, 1]),
+            Some(vec![vec![0, 1], vec![3, 4]])
+        );
+        assert_eq!(placements(&parse(".#..."), &[3]), Some(vec![vec![0, 1]]));
+        assert_eq!(placements(&parse("##x##"), &[3]), None);
+        assert_eq!(placements(&parse("....."), &[]), Some(vec![]));
+        assert_eq!(placements(&parse("..#.."), &[]), None);
+        assert_eq!(placements(&parse("....."), &[6]), None);
+    }
+
+    #[test]
+    fn test_satisfied() {
+        // 列の途中のブロックでも位置が確定すれば満たしている
+        assert_eq!(
+            satisfied(&parse("..x##x.."), &[1, 2, 1]),
+            vec![false, true, false]
+        );
+        // どのブロックか決まらなければ満たしていない
+        assert_eq!(satisfied(&parse("..x#x..."), &[1, 1]), vec![false, false]);
+        assert_eq!(satisfied(&parse("#x......"), &[1, 1]), vec![true, false]);
+        // 塗りが足りなければ確定していない
+        assert_eq!(satisfied(&parse("x#....x"), &[5]), vec![false]);
+        assert_eq!(satisfied(&parse("##x#x"), &[2, 1]), vec![true, true]);
+        // 矛盾していれば何も満たさない
+        assert_eq!(satisfied(&parse("###x#"), &[2, 1]), vec![false, false]);
+        assert!(!is_consistent(&parse("###x#"), &[2, 1]));
+        assert!(is_consistent(&parse("#x..."), &[1, 2]));
+        // 塗りすぎでなくても、バツで区切られて置けなければ矛盾
+        assert!(!is_consistent(&parse(".x.x."), &[2]));
+    }
+}
@@ -1,14 +1,27 @@
-use illu_logi::{gen, vis_grid};
+use illu_logi::{
+    board_hints, gen, gen_code_plain, image_board_from_bytes, png_board, png_grid, print_entries,
+    print_pdf_inner, print_svg_inner, search_seeds, tikz, vis_grid, Filter, GenConstraints,
+    PrintOptions, Theme,
+};
 fn main() {
-    vis();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(|s| s.as_str()) {
+        Some("image") => image(&args[1..]),
+        Some("search") => search(&args[1..]),
+        Some("png") => png(&args[1..]),
+        Some("print") => print(&args[1..]),
+        Some("tikz") => tikz_export(&args[1..]),
+        _ => vis(),
+    }
 }
 
+#[allow(dead_code)]
 fn test() {
     let mut sum = std::time::Duration::new(0, 0);
     let n = 20;
     for seed in 0..100 {
         let start = std::time::Instant::now();
-        gen(n, n, seed);
+        gen(n, n, seed, 0, 0, 0.0, &GenConstraints::default()).unwrap();
         let duration = start.elapsed();
         sum += duration;
         println!("seed = {} : {:?}", seed, duration);
@@ -20,8 +33,215 @@ fn vis() {
         n:usize,
         seed:u64,
     }
-    let board = gen(n, n, seed);
-    let svg = vis_grid(n, n, 24, board);
+    let board = gen(n, n, seed, 0, 0, 0.0, &GenConstraints::default()).unwrap();
+    let svg = vis_grid(n, n, board, &Theme::new());
+    let vis = format!("<html><body>{}</body></html>", svg);
+    std::fs::write("vis.html", &vis).unwrap();
+}
+
+/// image <path> <h> <w> [--threshold <0-255>] [--dither] [--givens]
+fn image(args: &[String]) {
+    const USAGE: &str =
+        "usage: illu-logi image <path> <h> <w> [--threshold <0-255>] [--dither] [--givens]";
+    if args.len() < 3 {
+        eprintln!("{}", USAGE);
+        std::process::exit(1);
+    }
+    let (h, w) = (args[1].parse().expect(USAGE), args[2].parse().expect(USAGE));
+    let mut threshold = -1;
+    let mut dither = false;
+    let mut with_givens = false;
+    let mut rest = args[3..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--threshold" => threshold = rest.next().and_then(|t| t.parse().ok()).expect(USAGE),
+            "--dither" => dither = true,
+            "--givens" => with_givens = true,
+            _ => panic!("{}", USAGE),
+        }
+    }
+
+    let bytes = std::fs::read(&args[0]).unwrap();
+    let board = match image_board_from_bytes(h, w, &bytes, threshold, dither, with_givens) {
+        Ok(board) => board,
+        Err(err) => {
+            eprintln!("{}: {}", args[0], err);
+            std::process::exit(1);
+        }
+    };
+    let ambiguous = board.ambiguous();
+    if ambiguous.is_empty() {
+        println!("unique");
+    } else {
+        println!("ambiguous cells: {}", ambiguous.len());
+        for i in ambiguous {
+            println!("{} {}", i as usize / w, i as usize % w);
+        }
+        if with_givens {
+            println!("givens needed: {}", board.givens().len());
+        }
+    }
+    let svg = vis_grid(h, w, board.board(), &Theme::new());
     let vis = format!("<html><body>{}</body></html>", svg);
     std::fs::write("vis.html", &vis).unwrap();
 }
+
+/// search <h> <w> [--from <seed>] [--count <n>] [--limit <n>] [条件...]
+/// 見つかった seed と統計を seed 順に出力し、最後に続きの seed を出力する
+fn search(args: &[String]) {
+    const USAGE: &str =
+        "usage: illu-logi search <h> <w> [--from <seed>] [--count <n>] [--limit <n>] \
+        [--min-density <f>] [--max-density <f>] [--difficulty easy|medium|hard] \
+        [--max-clue <n>] [--max-clues-per-line <n>] [--symmetry <0-5>] [--pattern <0-3>]";
+    if args.len() < 2 {
+        eprintln!("{}", USAGE);
+        std::process::exit(1);
+    }
+    let (h, w): (usize, usize) = (args[0].parse().expect(USAGE), args[1].parse().expect(USAGE));
+    let (mut from, mut count, mut limit) = (0_u64, 10000_u64, usize::MAX);
+    let (mut symmetry, mut pattern) = (0, 0);
+    let mut filter = Filter::default();
+    let mut rest = args[2..].iter();
+    while let Some(arg) = rest.next() {
+        let value = rest.next().expect(USAGE);
+        match arg.as_str() {
+            "--from" => from = value.parse().expect(USAGE),
+            "--count" => count = value.parse().expect(USAGE),
+            "--limit" => limit = value.parse().expect(USAGE),
+            "--min-density" => filter.min_density = Some(value.parse().expect(USAGE)),
+            "--max-density" => filter.max_density = Some(value.parse().expect(USAGE)),
+            "--difficulty" => filter.difficulty = Some(value.parse().expect(USAGE)),
+            "--max-clue" => filter.max_clue = Some(value.parse().expect(USAGE)),
+            "--max-clues-per-line" => filter.max_clues_per_line = Some(value.parse().expect(USAGE)),
+            "--symmetry" => symmetry = value.parse().expect(USAGE),
+            "--pattern" => pattern = value.parse().expect(USAGE),
+            _ => panic!("{}", USAGE),
+        }
+    }
+    let min_smoothness = if pattern == 0 { 0.0 } else { 0.7 };
+
+    // 一定数ずつ並列に調べ、見つかった順に出力する
+    const BATCH: u64 = 256;
+    let end = from.saturating_add(count);
+    let mut found = 0;
+    let mut next = from;
+    while next < end && found < limit {
+        let batch = next..end.min(next + BATCH);
+        next = batch.end;
+        let found_seeds = search_seeds(h, w, symmetry, pattern, min_smoothness, &filter, batch)
+            .unwrap_or_else(|err| panic!("{}", err));
+        for (seed, stats) in found_seeds {
+            println!("{} {}", seed, stats);
+            found += 1;
+            if found == limit {
+                next = seed + 1;
+                break;
+            }
+        }
+    }
+    println!("next: {}", next);
+}
+
+/// png <n> <seed> <out> [--puzzle] [--cell-size <n>]
+/// 解答の画像を書き出す。`--puzzle` なら手がかり付きの空の盤面。`seed` は `v1-42` のような seed コード
+fn png(args: &[String]) {
+    const USAGE: &str = "usage: illu-logi png <n> <seed> <out> [--puzzle] [--cell-size <n>]";
+    if args.len() < 3 {
+        eprintln!("{}", USAGE);
+        std::process::exit(1);
+    }
+    let n: usize = args[0].parse().expect(USAGE);
+    let mut puzzle = false;
+    let mut theme = Theme::new();
+    let mut rest = args[3..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--puzzle" => puzzle = true,
+            "--cell-size" => {
+                theme.set_cell_size(rest.next().and_then(|d| d.parse().ok()).expect(USAGE))
+            }
+            _ => panic!("{}", USAGE),
+        }
+    }
+    let board = gen_code_plain(n, n, &args[1]).unwrap_or_else(|err| panic!("{}", err));
+    let png = if puzzle {
+        let hints = board_hints(n, n, board);
+        png_board(n, n, vec![2; n * n], hints, vec![], &theme)
+    } else {
+        png_grid(n, n, board, &theme)
+    };
+    std::fs::write(&args[2], png).unwrap();
+}
+
+/// print <n> <out> <seed>... [--paper a4|letter] [--per-page <n>] [--title <s>] [--no-answers]
+/// `out` が `.pdf` で終われば PDF を、そうでなければ `<out>-<page>.svg` を書き出す
+fn print(args: &[String]) {
+    const USAGE: &str = "usage: illu-logi print <n> <out> <seed>... [--paper a4|letter] \
+        [--per-page <n>] [--title <s> (ASCII only for PDF)] [--no-answers]";
+    if args.len() < 3 {
+        eprintln!("{}", USAGE);
+        std::process::exit(1);
+    }
+    let n: usize = args[0].parse().expect(USAGE);
+    let out = &args[1];
+    let mut codes = vec![];
+    let mut options = PrintOptions::default();
+    let mut rest = args[2..].iter();
+    while let Some(arg) = rest.next() {
+        let mut value = || rest.next().expect(USAGE);
+        match arg.as_str() {
+            "--paper" => options.paper = value().parse().expect(USAGE),
+            "--per-page" => options.per_page = value().parse().expect(USAGE),
+            "--title" => options.title = value().clone(),
+            "--no-answers" => options.answers = false,
+            flag if flag.starts_with("--") => panic!("{}", USAGE),
+            code => codes.push(code),
+        }
+    }
+    let entries = match print_entries(n, n, &codes.join(" ")) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    if out.ends_with(".pdf") {
+        match print_pdf_inner(&entries, &options) {
+            Ok(pdf) => std::fs::write(out, pdf).unwrap(),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        for (i, page) in print_svg_inner(&entries, &options).iter().enumerate() {
+            std::fs::write(format!("{}-{}.svg", out, i + 1), page).unwrap();
+        }
+    }
+}
+
+/// tikz <n> <seed> [--solution] [--cell-size <cm>]
+/// 問題を TikZ で書いた LaTeX の文書として出力する。`seed` は `png` と同じ seed コード
+fn tikz_export(args: &[String]) {
+    const USAGE: &str = "usage: illu-logi tikz <n> <seed> [--solution] [--cell-size <cm>]";
+    if args.len() < 2 {
+        eprintln!("{}", USAGE);
+        std::process::exit(1);
+    }
+    let n: usize = args[0].parse().expect(USAGE);
+    let (mut solution, mut cell_size) = (false, 0.5);
+    let mut rest = args[2..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--solution" => solution = true,
+            "--cell-size" => cell_size = rest.next().and_then(|d| d.parse().ok()).expect(USAGE),
+            _ => panic!("{}", USAGE),
+        }
+    }
+    let board = gen_code_plain(n, n, &args[1]).unwrap_or_else(|err| panic!("{}", err));
+    let hints = board_hints(n, n, board.clone());
+    print!(
+        "{}",
+        tikz(n, n, board, hints, solution, cell_size, &Theme::new())
+    );
+}
@@ -0,0 +1,195 @@
+use crate::raster::to_rgb;
+use std::fmt::Write;
+
+/// Helvetica の数字の幅 (フォントサイズに対する比)。数字以外もおおよそこのくらい
+const CHAR_WIDTH: f64 = 0.556;
+
+/// 1 ページぶんの描画命令。座標は SVG と同じく左上が原点で、単位は pt
+pub struct PdfPage {
+    width: f64,
+    height: f64,
+    ops: String,
+}
+
+fn color_ops(color: &str, op: &str) -> String {
+    let [r, g, b] = to_rgb(color);
+    format!(
+        "{:.3} {:.3} {:.3} {}",
+        r as f64 / 255.0,
+        g as f64 / 255.0,
+        b as f64 / 255.0,
+        op
+    )
+}
+
+/// Helvetica の標準の符号化で書けるか。印字できる ASCII 以外は `?` になってしまう
+pub fn encodable(text: &str) -> bool {
+    text.chars().all(|c| (' '..='~').contains(&c))
+}
+
+/// 文字列のおおよその幅
+pub fn measure(text: &str, font_size: f64) -> f64 {
+    text.chars().count() as f64 * font_size * CHAR_WIDTH
+}
+
+impl PdfPage {
+    pub fn new(width: f64, height: f64) -> Self {
+        Self {
+            width,
+            height,
+            ops: String::new(),
+        }
+    }
+
+    pub fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: &str) {
+        let y = self.height - y - height;
+        writeln!(
+            self.ops,
+            "{} {:.2} {:.2} {:.2} {:.2} re f",
+            color_ops(color, "rg"),
+            x,
+            y,
+            width,
+            height
+        )
+        .unwrap();
+    }
+
+    pub fn stroke_rect(&mut self, x: f64, y: f64, width: f64, height: f64, t: f64, color: &str) {
+        let y = self.height - y - height;
+        writeln!(
+            self.ops,
+            "{} {:.2} w {:.2} {:.2} {:.2} {:.2} re S",
+            color_ops(color, "RG"),
+            t,
+            x,
+            y,
+            width,
+            height
+        )
+        .unwrap();
+    }
+
+    pub fn line(&mut self, (x0, y0): (f64, f64), (x1, y1): (f64, f64), t: f64, color: &str) {
+        writeln!(
+            self.ops,
+            "{} {:.2} w {:.2} {:.2} m {:.2} {:.2} l S",
+            color_ops(color, "RG"),
+            t,
+            x0,
+            self.height - y0,
+            x1,
+            self.height - y1
+        )
+        .unwrap();
+    }
+
+    /// `(x, y)` を中心に描く。Helvetica にない文字は `?` にする
+    pub fn text(&mut self, x: f64, y: f64, text: &str, font_size: f64, color: &str) {
+        self.text_left(
+            x - measure(text, font_size) / 2.0,
+            y,
+            text,
+            font_size,
+            color,
+        );
+    }
+
+    /// 左端を `x`、縦の中心を `y` に合わせて描く
+    pub fn text_left(&mut self, x: f64, y: f64, text: &str, font_size: f64, color: &str) {
+        let escaped = text
+            .chars()
+            .map(|c| match c {
+                '(' | ')' | '\\' => format!("\\{}", c),
+                ' '..='~' => c.to_string(),
+                _ => "?".to_string(),
+            })
+            .collect::<String>();
+        // 大文字の高さのおおよそ半分だけベースラインを下げる
+        let baseline = self.height - y - font_size * 0.35;
+        writeln!(
+            self.ops,
+            "BT {} /F1 {:.2} Tf {:.2} {:.2} Td ({}) Tj ET",
+            color_ops(color, "rg"),
+            font_size,
+            x,
+            baseline,
+            escaped
+        )
+        .unwrap();
+    }
+}
+
+/// ページを順に並べた PDF を作る。フォントは Helvetica だけを使う
+pub fn write_pdf(pages: &[PdfPage]) -> Vec<u8> {
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len())
+                .map(|i| format!("{} 0 R", 4 + 2 * i))
+                .collect::<Vec<_>>()
+                .join(" "),
+            pages.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+    ];
+    for (i, page) in pages.iter().enumerate() {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+             /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            page.width,
+            page.height,
+            5 + 2 * i
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            page.ops.len(),
+            page.ops
+        ));
+    }
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = vec![];
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        write!(pdf, "{} 0 obj\n{}\nendobj\n", i + 1, object).unwrap();
+    }
+    let xref = pdf.len();
+    write!(pdf, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).unwrap();
+    for offset in offsets {
+        writeln!(pdf, "{:010} 00000 n ", offset).unwrap();
+    }
+    write!(
+        pdf,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    )
+    .unwrap();
+    pdf.into_bytes()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_write_pdf() {
+        let mut page = PdfPage::new(100.0, 200.0);
+        page.fill_rect(10.0, 20.0, 30.0, 40.0, "black");
+        page.text(50.0, 50.0, "(1)", 10.0, "#ff0000");
+        let pdf = String::from_utf8(write_pdf(&[page, PdfPage::new(100.0, 200.0)])).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4\n") && pdf.ends_with("%%EOF\n"));
+        // 上下を反転した座標になる
+        assert!(pdf.contains("10.00 140.00 30.00 40.00 re f"));
+        assert!(pdf.contains(r"(\(1\)) Tj"));
+        assert!(pdf.contains("/Count 2"));
+        // xref の位置が合っている
+        let xref = pdf.rfind("startxref\n").unwrap();
+        let offset: usize = pdf[xref + 10..].lines().next().unwrap().parse().unwrap();
+        assert!(pdf[offset..].starts_with("xref\n0 8\n"));
+        let first = pdf.find("1 0 obj").unwrap();
+        assert!(pdf.contains(&format!("{:010} 00000 n ", first)));
+    }
+}
@@ -0,0 +1,516 @@
+use crate::gen::{GenError, GenOptions};
+use crate::layout::Layout;
+use crate::pdf::{self, PdfPage};
+use crate::search::{stats, Difficulty};
+use crate::seed::SeedCode;
+use crate::vis::{board_document, grid_document, RenderOptions};
+use std::str::FromStr;
+use svg::node::element::{Group, Rectangle, Text};
+use svg::Document;
+
+/// 用紙の大きさ
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Paper {
+    A4,
+    Letter,
+}
+
+impl Paper {
+    /// 幅と高さ (pt)
+    pub fn size(self) -> (f64, f64) {
+        match self {
+            Paper::A4 => (595.28, 841.89),
+            Paper::Letter => (612.0, 792.0),
+        }
+    }
+}
+
+impl FromStr for Paper {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "a4" => Ok(Paper::A4),
+            "letter" => Ok(Paper::Letter),
+            _ => Err(format!("unknown paper: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrintOptions {
+    pub paper: Paper,
+    /// 1 ページに載せる問題の数
+    pub per_page: usize,
+    /// 各ページの上に書く見出し
+    pub title: String,
+    /// 最後に解答のページを付ける
+    pub answers: bool,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            paper: Paper::A4,
+            per_page: 1,
+            title: String::new(),
+            answers: true,
+        }
+    }
+}
+
+/// 印刷する問題 1 つ
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrintEntry {
+    pub h: usize,
+    pub w: usize,
+    pub code: SeedCode,
+    pub difficulty: Difficulty,
+    pub grid: Vec<Vec<bool>>,
+    pub hints: (Vec<Vec<i32>>, Vec<Vec<i32>>),
+}
+
+impl PrintEntry {
+    pub fn new(h: usize, w: usize, code: SeedCode) -> Result<Self, GenError> {
+        let (grid, hints) = code.generate(h, w, &GenOptions::default())?;
+        let difficulty = stats(h, w, &grid, &hints).difficulty;
+        let to_i32 = |hints: &[Vec<usize>]| {
+            hints
+                .iter()
+                .map(|hints| hints.iter().map(|&num| num as i32).collect())
+                .collect()
+        };
+        Ok(Self {
+            h,
+            w,
+            code,
+            difficulty,
+            grid: grid.chunks(w).map(|row| row.to_vec()).collect(),
+            hints: (to_i32(&hints.0), to_i32(&hints.1)),
+        })
+    }
+
+    fn caption(&self, i: usize) -> String {
+        format!(
+            "No.{}  {}x{}  {}  {}",
+            i + 1,
+            self.h,
+            self.w,
+            self.difficulty,
+            self.code
+        )
+    }
+}
+
+/// 空白かカンマで区切った seed を順に問題にする
+pub fn print_entries(h: usize, w: usize, codes: &str) -> Result<Vec<PrintEntry>, String> {
+    codes
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|code| !code.is_empty())
+        .map(|code| {
+            let code = code.parse::<SeedCode>().map_err(|err| err.to_string())?;
+            PrintEntry::new(h, w, code).map_err(|err| err.to_string())
+        })
+        .collect()
+}
+
+/// 印刷向けの描き方。白地に黒で、線は細めにする
+pub fn print_render_options() -> RenderOptions {
+    let mut options = RenderOptions {
+        font_family: Some("Helvetica, Arial, sans-serif".to_string()),
+        grid_width: 0.5,
+        cross_width: 0.5,
+        frame_width: 2.0,
+        // 1 ページに盤面をいくつも並べるので id を付けない
+        compact: true,
+        ..Default::default()
+    };
+    options.palette.clue_satisfied = options.palette.clue.clone();
+    options
+}
+
+const MARGIN: f64 = 36.0;
+const TITLE_SIZE: f64 = 16.0;
+const CAPTION_SIZE: f64 = 9.0;
+/// 見出しと問題の間、問題どうしの間
+const GAP: f64 = 18.0;
+/// 解答のページに載せる数 (横, 縦)
+const ANSWER_GRID: (usize, usize) = (3, 4);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SlotKind {
+    Puzzle,
+    Answer,
+}
+
+/// ページ上で問題 1 つを置く場所
+#[derive(Clone, Debug, PartialEq)]
+struct Slot {
+    entry: usize,
+    kind: SlotKind,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Page {
+    heading: String,
+    slots: Vec<Slot>,
+}
+
+/// `n` 個を縦横に並べるときの (横, 縦) の数。縦長の用紙なので縦を多めにする
+fn grid_shape(n: usize) -> (usize, usize) {
+    let cols = ((n as f64).sqrt().floor() as usize).max(1);
+    (cols, n.div_ceil(cols))
+}
+
+fn plan(n: usize, options: &PrintOptions) -> Vec<Page> {
+    let (width, height) = options.paper.size();
+    let top = MARGIN + TITLE_SIZE + GAP;
+    // 下の余白にはページ番号を書く
+    let (area_width, area_height) = (width - 2.0 * MARGIN, height - top - MARGIN - GAP);
+    let pages = |kind: SlotKind, per_page: usize, (cols, rows): (usize, usize), heading: &str| {
+        let slot_width = (area_width - GAP * (cols - 1) as f64) / cols as f64;
+        let slot_height = (area_height - GAP * (rows - 1) as f64) / rows as f64;
+        (0..n)
+            .collect::<Vec<_>>()
+            .chunks(per_page)
+            .map(|entries| Page {
+                heading: heading.to_string(),
+                slots: entries
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &entry)| Slot {
+                        entry,
+                        kind,
+                        x: MARGIN + (i % cols) as f64 * (slot_width + GAP),
+                        y: top + (i / cols) as f64 * (slot_height + GAP),
+                        width: slot_width,
+                        height: slot_height,
+                    })
+                    .collect(),
+            })
+            .collect::<Vec<_>>()
+    };
+    let per_page = options.per_page.max(1);
+    let mut ret = pages(
+        SlotKind::Puzzle,
+        per_page,
+        grid_shape(per_page),
+        &options.title,
+    );
+    if options.answers {
+        let heading = if options.title.is_empty() {
+            "Answers".to_string()
+        } else {
+            format!("{} - Answers", options.title)
+        };
+        let per_page = ANSWER_GRID.0 * ANSWER_GRID.1;
+        ret.extend(pages(SlotKind::Answer, per_page, ANSWER_GRID, &heading));
+    }
+    ret
+}
+
+/// 盤面を枠に収める拡大率と、中央に寄せたときの左上
+fn fit(slot: &Slot, width: f64, height: f64) -> (f64, f64, f64) {
+    let avail = slot.height - CAPTION_SIZE - GAP / 2.0;
+    let scale = (slot.width / width).min(avail / height);
+    let x = slot.x + (slot.width - width * scale) / 2.0;
+    (scale, x, slot.y + CAPTION_SIZE + GAP / 2.0)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn svg_text(x: f64, y: f64, text: &str, font_size: f64, anchor: &str) -> Text {
+    Text::new()
+        .set("x", x)
+        .set("y", y)
+        .set("font-family", "Helvetica, Arial, sans-serif")
+        .set("font-size", font_size)
+        .set("text-anchor", anchor)
+        .set("dominant-baseline", "central")
+        .add(svg::node::Text::new(escape(text)))
+}
+
+/// 1 ページに 1 つの SVG を返す。問題のページのあとに解答のページが続く
+pub fn print_svg_inner(entries: &[PrintEntry], options: &PrintOptions) -> Vec<String> {
+    let (width, height) = options.paper.size();
+    let render = print_render_options();
+    let pages = plan(entries.len(), options);
+    let total = pages.len();
+    pages
+        .iter()
+        .enumerate()
+        .map(|(number, page)| {
+            let mut doc = Document::new()
+                .set("viewBox", (0, 0, width, height))
+                .set("width", format!("{}pt", width))
+                .set("height", format!("{}pt", height))
+                .add(
+                    Rectangle::new()
+                        .set("fill", "white")
+                        .set("width", width)
+                        .set("height", height),
+                )
+                .add(svg_text(
+                    MARGIN,
+                    MARGIN + TITLE_SIZE / 2.0,
+                    &page.heading,
+                    TITLE_SIZE,
+                    "start",
+                ))
+                .add(svg_text(
+                    width / 2.0,
+                    height - MARGIN / 2.0,
+                    &format!("{} / {}", number + 1, total),
+                    CAPTION_SIZE,
+                    "middle",
+                ));
+            for slot in &page.slots {
+                let entry = &entries[slot.entry];
+                let (h, w) = (entry.h, entry.w);
+                let (caption, board, board_width, board_height) = match slot.kind {
+                    SlotKind::Puzzle => {
+                        let layout = Layout::new(h, w, &entry.hints, &render);
+                        let board = vec![vec![None; w]; h];
+                        let givens = vec![vec![false; w]; h];
+                        let svg =
+                            board_document(h, w, &board, &entry.hints, &givens, &layout, &render);
+                        (entry.caption(slot.entry), svg, layout.width, layout.height)
+                    }
+                    SlotKind::Answer => {
+                        let board = entry
+                            .grid
+                            .iter()
+                            .map(|row| row.iter().map(|&f| Some(f)).collect::<Vec<_>>())
+                            .collect::<Vec<_>>();
+                        let svg = grid_document(h, w, &board, &render);
+                        let d = render.cell_size;
+                        (
+                            format!("No.{}", slot.entry + 1),
+                            svg,
+                            d * w as i32,
+                            d * h as i32,
+                        )
+                    }
+                };
+                let (scale, x, y) = fit(slot, board_width as f64, board_height as f64);
+                doc = doc
+                    .add(svg_text(
+                        slot.x,
+                        slot.y + CAPTION_SIZE / 2.0,
+                        &caption,
+                        CAPTION_SIZE,
+                        "start",
+                    ))
+                    .add(
+                        Group::new()
+                            .set(
+                                "transform",
+                                format!("translate({} {}) scale({})", x, y, scale),
+                            )
+                            .add(board),
+                    );
+                if slot.kind == SlotKind::Answer {
+                    // 解答は枠で囲む
+                    doc = doc.add(
+                        Rectangle::new()
+                            .set("x", x)
+                            .set("y", y)
+                            .set("width", board_width as f64 * scale)
+                            .set("height", board_height as f64 * scale)
+                            .set("fill", "none")
+                            .set("stroke", "black")
+                            .set("stroke-width", render.grid_width),
+                    );
+                }
+            }
+            doc.to_string()
+        })
+        .collect()
+}
+
+/// 空の盤面と手がかりを PDF に描く。`vis_board_inner` と同じ配置にする
+fn pdf_board(
+    page: &mut PdfPage,
+    entry: &PrintEntry,
+    layout: &Layout,
+    (scale, left, top): (f64, f64, f64),
+    options: &RenderOptions,
+) {
+    let palette = &options.palette;
+    let (h, w) = (entry.h, entry.w);
+    let d = layout.cell_size as f64 * scale;
+    let ox = left + layout.origin_x as f64 * scale;
+    let oy = top + layout.origin_y as f64 * scale;
+    page.fill_rect(ox, oy, d * w as f64, d * h as f64, &palette.background);
+    let grid_width = options.grid_width * scale;
+    for y in 0..=h {
+        let py = oy + d * y as f64;
+        page.line((ox, py), (ox + d * w as f64, py), grid_width, &palette.grid);
+    }
+    for x in 0..=w {
+        let px = ox + d * x as f64;
+        page.line((px, oy), (px, oy + d * h as f64), grid_width, &palette.grid);
+    }
+    let interval = options.major_interval;
+    for y in (0..h).step_by(interval) {
+        for x in (0..w).step_by(interval) {
+            let size = d * interval as f64;
+            let (px, py) = (ox + d * x as f64, oy + d * y as f64);
+            let t = options.frame_width * scale;
+            page.stroke_rect(px, py, size, size, t, &palette.frame);
+        }
+    }
+    let clues = layout
+        .rows
+        .iter()
+        .zip(&entry.hints.0)
+        .chain(layout.columns.iter().zip(&entry.hints.1));
+    for (boxes, hints) in clues {
+        for (clue, num) in boxes.iter().zip(hints) {
+            page.text(
+                left + clue.x * scale,
+                top + clue.y * scale,
+                &num.to_string(),
+                clue.font_size * scale,
+                &palette.clue,
+            );
+        }
+    }
+}
+
+/// `print_svg_inner` と同じページを PDF にする。題名は印字できる ASCII だけ
+pub fn print_pdf_inner(entries: &[PrintEntry], options: &PrintOptions) -> Result<Vec<u8>, String> {
+    if !pdf::encodable(&options.title) {
+        return Err(
+            "the PDF title must be printable ASCII; use the SVG output instead".to_string(),
+        );
+    }
+    let (width, height) = options.paper.size();
+    let render = print_render_options();
+    let pages = plan(entries.len(), options);
+    let total = pages.len();
+    let pages = pages
+        .iter()
+        .enumerate()
+        .map(|(number, page)| {
+            let mut pdf = PdfPage::new(width, height);
+            let title_y = MARGIN + TITLE_SIZE / 2.0;
+            pdf.text_left(MARGIN, title_y, &page.heading, TITLE_SIZE, "black");
+            let footer = format!("{} / {}", number + 1, total);
+            pdf.text(
+                width / 2.0,
+                height - MARGIN / 2.0,
+                &footer,
+                CAPTION_SIZE,
+                "black",
+            );
+            for slot in &page.slots {
+                let entry = &entries[slot.entry];
+                let caption_y = slot.y + CAPTION_SIZE / 2.0;
+                match slot.kind {
+                    SlotKind::Puzzle => {
+                        let caption = entry.caption(slot.entry);
+                        pdf.text_left(slot.x, caption_y, &caption, CAPTION_SIZE, "black");
+                        let layout = Layout::new(entry.h, entry.w, &entry.hints, &render);
+                        let fit = fit(slot, layout.width as f64, layout.height as f64);
+                        pdf_board(&mut pdf, entry, &layout, fit, &render);
+                    }
+                    SlotKind::Answer => {
+                        let caption = format!("No.{}", slot.entry + 1);
+                        pdf.text_left(slot.x, caption_y, &caption, CAPTION_SIZE, "black");
+                        let (h, w) = (entry.h, entry.w);
+                        let d = render.cell_size as f64;
+                        let (scale, x, y) = fit(slot, d * w as f64, d * h as f64);
+                        let d = d * scale;
+                        for (cy, row) in entry.grid.iter().enumerate() {
+                            for (cx, &f) in row.iter().enumerate() {
+                                if f {
+                                    let (px, py) = (x + d * cx as f64, y + d * cy as f64);
+                                    pdf.fill_rect(px, py, d, d, &render.palette.fill);
+                                }
+                            }
+                        }
+                        let t = render.grid_width;
+                        pdf.stroke_rect(x, y, d * w as f64, d * h as f64, t, "black");
+                    }
+                }
+            }
+            pdf
+        })
+        .collect::<Vec<_>>();
+    Ok(pdf::write_pdf(&pages))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entries(n: usize) -> Vec<PrintEntry> {
+        let codes = (0..n)
+            .map(|seed| format!("v1-{}", seed))
+            .collect::<Vec<_>>();
+        print_entries(10, 10, &codes.join(", ")).unwrap()
+    }
+
+    #[test]
+    fn test_plan() {
+        assert_eq!(grid_shape(1), (1, 1));
+        assert_eq!(grid_shape(2), (1, 2));
+        assert_eq!(grid_shape(4), (2, 2));
+        assert_eq!(grid_shape(6), (2, 3));
+        let options = PrintOptions {
+            per_page: 4,
+            ..Default::default()
+        };
+        // 問題 3 ページと解答 1 ページ
+        let pages = plan(9, &options);
+        assert_eq!(pages.len(), 4);
+        assert_eq!(pages[2].slots.len(), 1);
+        assert_eq!(pages[3].slots.len(), 9);
+        assert_eq!(pages[3].heading, "Answers");
+        let (width, height) = Paper::A4.size();
+        for slot in pages.iter().flat_map(|page| &page.slots) {
+            assert!(slot.x >= MARGIN && slot.x + slot.width <= width - MARGIN + 1e-9);
+            assert!(slot.y >= MARGIN && slot.y + slot.height <= height - MARGIN);
+        }
+    }
+
+    #[test]
+    fn test_print() {
+        let entries = entries(3);
+        assert_eq!(entries[2].code, SeedCode::new(2));
+        assert!(print_entries(10, 10, "v9-1").is_err());
+        assert_eq!("Letter".parse(), Ok(Paper::Letter));
+        let options = PrintOptions {
+            paper: Paper::Letter,
+            per_page: 2,
+            title: "Event <1>".to_string(),
+            answers: true,
+        };
+        let pages = print_svg_inner(&entries, &options);
+        assert_eq!(pages.len(), 3);
+        assert!(pages[0].contains("Event &lt;1&gt;"));
+        assert!(pages[0].contains(&format!("No.1  10x10  {}  v1-0", entries[0].difficulty)));
+        assert!(pages[2].contains("Event &lt;1&gt; - Answers"));
+        assert!(pages[0].contains(r#"width="612pt""#));
+        // 盤面を並べても id が重ならない
+        assert!(pages.iter().all(|page| !page.contains("id=")));
+
+        let pdf = print_pdf_inner(&entries, &options).unwrap();
+        let pdf = String::from_utf8(pdf).unwrap();
+        assert!(pdf.contains("/Count 3"));
+        assert!(pdf.contains("(Event <1>) Tj"));
+        let options = PrintOptions {
+            title: "餃子".to_string(),
+            ..options
+        };
+        assert!(print_pdf_inner(&entries, &options).is_err());
+    }
+}
@@ -0,0 +1,538 @@
+use crate::font;
+use crate::layout::Layout;
+use crate::vis::{cell_fill, clue_fills, RenderOptions};
+
+pub type Rgb = [u8; 3];
+
+/// CSS の色の名前 (CSS Color Module Level 4 の 148 個)
+const NAMED_COLORS: &[(&str, Rgb)] = &[
+    ("aliceblue", [240, 248, 255]),
+    ("antiquewhite", [250, 235, 215]),
+    ("aqua", [0, 255, 255]),
+    ("aquamarine", [127, 255, 212]),
+    ("azure", [240, 255, 255]),
+    ("beige", [245, 245, 220]),
+    ("bisque", [255, 228, 196]),
+    ("black", [0, 0, 0]),
+    ("blanchedalmond", [255, 235, 205]),
+    ("blue", [0, 0, 255]),
+    ("blueviolet", [138, 43, 226]),
+    ("brown", [165, 42, 42]),
+    ("burlywood", [222, 184, 135]),
+    ("cadetblue", [95, 158, 160]),
+    ("chartreuse", [127, 255, 0]),
+    ("chocolate", [210, 105, 30]),
+    ("coral", [255, 127, 80]),
+    ("cornflowerblue", [100, 149, 237]),
+    ("cornsilk", [255, 248, 220]),
+    ("crimson", [220, 20, 60]),
+    ("cyan", [0, 255, 255]),
+    ("darkblue", [0, 0, 139]),
+    ("darkcyan", [0, 139, 139]),
+    ("darkgoldenrod", [184, 134, 11]),
+    ("darkgray", [169, 169, 169]),
+    ("darkgreen", [0, 100, 0]),
+    ("darkgrey", [169, 169, 169]),
+    ("darkkhaki", [189, 183, 107]),
+    ("darkmagenta", [139, 0, 139]),
+    ("darkolivegreen", [85, 107, 47]),
+    ("darkorange", [255, 140, 0]),
+    ("darkorchid", [153, 50, 204]),
+    ("darkred", [139, 0, 0]),
+    ("darksalmon", [233, 150, 122]),
+    ("darkseagreen", [143, 188, 143]),
+    ("darkslateblue", [72, 61, 139]),
+    ("darkslategray", [47, 79, 79]),
+    ("darkslategrey", [47, 79, 79]),
+    ("darkturquoise", [0, 206, 209]),
+    ("darkviolet", [148, 0, 211]),
+    ("deeppink", [255, 20, 147]),
+    ("deepskyblue", [0, 191, 255]),
+    ("dimgray", [105, 105, 105]),
+    ("dimgrey", [105, 105, 105]),
+    ("dodgerblue", [30, 144, 255]),
+    ("firebrick", [178, 34, 34]),
+    ("floralwhite", [255, 250, 240]),
+    ("forestgreen", [34, 139, 34]),
+    ("fuchsia", [255, 0, 255]),
+    ("gainsboro", [220, 220, 220]),
+    ("ghostwhite", [248, 248, 255]),
+    ("gold", [255, 215, 0]),
+    ("goldenrod", [218, 165, 32]),
+    ("gray", [128, 128, 128]),
+    ("green", [0, 128, 0]),
+    ("greenyellow", [173, 255, 47]),
+    ("grey", [128, 128, 128]),
+    ("honeydew", [240, 255, 240]),
+    ("hotpink", [255, 105, 180]),
+    ("indianred", [205, 92, 92]),
+    ("indigo", [75, 0, 130]),
+    ("ivory", [255, 255, 240]),
+    ("khaki", [240, 230, 140]),
+    ("lavender", [230, 230, 250]),
+    ("lavenderblush", [255, 240, 245]),
+    ("lawngreen", [124, 252, 0]),
+    ("lemonchiffon", [255, 250, 205]),
+    ("lightblue", [173, 216, 230]),
+    ("lightcoral", [240, 128, 128]),
+    ("lightcyan", [224, 255, 255]),
+    ("lightgoldenrodyellow", [250, 250, 210]),
+    ("lightgray", [211, 211, 211]),
+    ("lightgreen", [144, 238, 144]),
+    ("lightgrey", [211, 211, 211]),
+    ("lightpink", [255, 182, 193]),
+    ("lightsalmon", [255, 160, 122]),
+    ("lightseagreen", [32, 178, 170]),
+    ("lightskyblue", [135, 206, 250]),
+    ("lightslategray", [119, 136, 153]),
+    ("lightslategrey", [119, 136, 153]),
+    ("lightsteelblue", [176, 196, 222]),
+    ("lightyellow", [255, 255, 224]),
+    ("lime", [0, 255, 0]),
+    ("limegreen", [50, 205, 50]),
+    ("linen", [250, 240, 230]),
+    ("magenta", [255, 0, 255]),
+    ("maroon", [128, 0, 0]),
+    ("mediumaquamarine", [102, 205, 170]),
+    ("mediumblue", [0, 0, 205]),
+    ("mediumorchid", [186, 85, 211]),
+    ("mediumpurple", [147, 112, 219]),
+    ("mediumseagreen", [60, 179, 113]),
+    ("mediumslateblue", [123, 104, 238]),
+    ("mediumspringgreen", [0, 250, 154]),
+    ("mediumturquoise", [72, 209, 204]),
+    ("mediumvioletred", [199, 21, 133]),
+    ("midnightblue", [25, 25, 112]),
+    ("mintcream", [245, 255, 250]),
+    ("mistyrose", [255, 228, 225]),
+    ("moccasin", [255, 228, 181]),
+    ("navajowhite", [255, 222, 173]),
+    ("navy", [0, 0, 128]),
+    ("oldlace", [253, 245, 230]),
+    ("olive", [128, 128, 0]),
+    ("olivedrab", [107, 142, 35]),
+    ("orange", [255, 165, 0]),
+    ("orangered", [255, 69, 0]),
+    ("orchid", [218, 112, 214]),
+    ("palegoldenrod", [238, 232, 170]),
+    ("palegreen", [152, 251, 152]),
+    ("paleturquoise", [175, 238, 238]),
+    ("palevioletred", [219, 112, 147]),
+    ("papayawhip", [255, 239, 213]),
+    ("peachpuff", [255, 218, 185]),
+    ("peru", [205, 133, 63]),
+    ("pink", [255, 192, 203]),
+    ("plum", [221, 160, 221]),
+    ("powderblue", [176, 224, 230]),
+    ("purple", [128, 0, 128]),
+    ("rebeccapurple", [102, 51, 153]),
+    ("red", [255, 0, 0]),
+    ("rosybrown", [188, 143, 143]),
+    ("royalblue", [65, 105, 225]),
+    ("saddlebrown", [139, 69, 19]),
+    ("salmon", [250, 128, 114]),
+    ("sandybrown", [244, 164, 96]),
+    ("seagreen", [46, 139, 87]),
+    ("seashell", [255, 245, 238]),
+    ("sienna", [160, 82, 45]),
+    ("silver", [192, 192, 192]),
+    ("skyblue", [135, 206, 235]),
+    ("slateblue", [106, 90, 205]),
+    ("slategray", [112, 128, 144]),
+    ("slategrey", [112, 128, 144]),
+    ("snow", [255, 250, 250]),
+    ("springgreen", [0, 255, 127]),
+    ("steelblue", [70, 130, 180]),
+    ("tan", [210, 180, 140]),
+    ("teal", [0, 128, 128]),
+    ("thistle", [216, 191, 216]),
+    ("tomato", [255, 99, 71]),
+    ("turquoise", [64, 224, 208]),
+    ("violet", [238, 130, 238]),
+    ("wheat", [245, 222, 179]),
+    ("white", [255, 255, 255]),
+    ("whitesmoke", [245, 245, 245]),
+    ("yellow", [255, 255, 0]),
+    ("yellowgreen", [154, 205, 50]),
+];
+
+/// `#rrggbb` / `#rgb` / `rgb(r, g, b)` / CSS の色の名前を読む。不透明度は無視する
+pub fn parse_color(color: &str) -> Option<Rgb> {
+    let color = color.trim();
+    if let Some(hex) = color.strip_prefix('#') {
+        let digits = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<Vec<_>>>();
+        match digits.as_deref() {
+            Some(&[r, g, b]) | Some(&[r, g, b, _]) => return Some([r * 17, g * 17, b * 17]),
+            Some(&[r1, r2, g1, g2, b1, b2]) | Some(&[r1, r2, g1, g2, b1, b2, _, _]) => {
+                return Some([r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2])
+            }
+            _ => {}
+        }
+    }
+    if let Some(rgb) = parse_rgb_function(color) {
+        return Some(rgb);
+    }
+    NAMED_COLORS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(color))
+        .map(|&(_, rgb)| rgb)
+}
+
+/// 描くときの色。`Theme` は読める色しか受け付けないので、読めないのは Rust から直接渡したときだけで、黒にする
+pub fn to_rgb(color: &str) -> Rgb {
+    parse_color(color).unwrap_or([0, 0, 0])
+}
+
+/// `rgb(255, 0, 0)` / `rgba(255, 0, 0, 0.5)` / `rgb(100% 0% 0% / 50%)` を読む
+fn parse_rgb_function(color: &str) -> Option<Rgb> {
+    let lower = color.to_ascii_lowercase();
+    let args = lower
+        .strip_prefix("rgba(")
+        .or_else(|| lower.strip_prefix("rgb("))?
+        .strip_suffix(')')?;
+    let mut parts = args
+        .split(|c: char| c == ',' || c == '/' || c.is_ascii_whitespace())
+        .filter(|part| !part.is_empty());
+    let mut channel = || -> Option<u8> {
+        let part = parts.next()?;
+        let value = match part.strip_suffix('%') {
+            Some(percent) => percent.parse::<f64>().ok()? / 100.0 * 255.0,
+            None => part.parse::<f64>().ok()?,
+        };
+        Some(value.clamp(0.0, 255.0).round() as u8)
+    };
+    let rgb = [channel()?, channel()?, channel()?];
+    // 4 つ目 (不透明度) より後ろがあれば読めない
+    if parts.nth(1).is_some() {
+        return None;
+    }
+    Some(rgb)
+}
+
+/// `base` の上に `color` を不透明度 `alpha` で重ねた色
+pub fn blend(base: Rgb, color: Rgb, alpha: f64) -> Rgb {
+    let mix = |b: u8, c: u8| (b as f64 * (1.0 - alpha) + c as f64 * alpha).round() as u8;
+    [
+        mix(base[0], color[0]),
+        mix(base[1], color[1]),
+        mix(base[2], color[2]),
+    ]
+}
+
+/// RGB の画像。矩形と線とビットマップフォントの文字だけを描ける
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Rgb>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize, background: Rgb) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![background; width * height],
+        }
+    }
+
+    /// はみ出した部分は切り捨てる
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: Rgb) {
+        let clamp = |v: i32, max: usize| v.clamp(0, max as i32) as usize;
+        let (x0, x1) = (clamp(x, self.width), clamp(x + width, self.width));
+        let (y0, y1) = (clamp(y, self.height), clamp(y + height, self.height));
+        for y in y0..y1 {
+            for x in x0..x1 {
+                self.pixels[y * self.width + x] = color;
+            }
+        }
+    }
+
+    /// 半透明の矩形を重ねる
+    pub fn blend_rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: Rgb, alpha: f64) {
+        let clamp = |v: i32, max: usize| v.clamp(0, max as i32) as usize;
+        let (x0, x1) = (clamp(x, self.width), clamp(x + width, self.width));
+        let (y0, y1) = (clamp(y, self.height), clamp(y + height, self.height));
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let pixel = &mut self.pixels[y * self.width + x];
+                *pixel = blend(*pixel, color, alpha);
+            }
+        }
+    }
+
+    /// SVG の stroke と同じく、線の中心が座標に来るように描く
+    pub fn line(&mut self, (x0, y0): (f64, f64), (x1, y1): (f64, f64), width: f64, color: Rgb) {
+        let t = width.round().max(1.0) as i32;
+        let steps = (x1 - x0).abs().max((y1 - y0).abs()).round().max(1.0) as i32;
+        if x0 == x1 || y0 == y1 {
+            let (x, y) = (x0.min(x1).round() as i32, y0.min(y1).round() as i32);
+            if x0 == x1 {
+                self.fill_rect(x - t / 2, y - t / 2, t, steps + t, color);
+            } else {
+                self.fill_rect(x - t / 2, y - t / 2, steps + t, t, color);
+            }
+            return;
+        }
+        // 斜めの線は太さぶんの正方形を並べる
+        for i in 0..=steps {
+            let r = i as f64 / steps as f64;
+            let x = (x0 + (x1 - x0) * r).round() as i32;
+            let y = (y0 + (y1 - y0) * r).round() as i32;
+            self.fill_rect(x - t / 2, y - t / 2, t, t, color);
+        }
+    }
+
+    /// 長方形の枠。線の中心が長方形の辺に来る
+    pub fn stroke_rect(&mut self, x: f64, y: f64, width: f64, height: f64, t: f64, color: Rgb) {
+        self.line((x, y), (x + width, y), t, color);
+        self.line((x, y + height), (x + width, y + height), t, color);
+        self.line((x, y), (x, y + height), t, color);
+        self.line((x + width, y), (x + width, y + height), t, color);
+    }
+
+    /// `(x, y)` を中心に、高さがおおよそ `font_size` の文字を描く。
+    /// フォントにない文字は飛ばす
+    pub fn text(&mut self, x: f64, y: f64, text: &str, font_size: f64, color: Rgb) {
+        let scale = (font_size / (font::HEIGHT + 1) as f64).round().max(1.0) as i32;
+        let glyphs = text.chars().filter_map(font::glyph).collect::<Vec<_>>();
+        // 文字の間は 1 ドット空ける
+        let width = glyphs.iter().map(|g| g[0].len() as i32 + 1).sum::<i32>() - 1;
+        let mut left = (x - (width * scale) as f64 / 2.0).round() as i32;
+        let top = (y - (font::HEIGHT as i32 * scale) as f64 / 2.0).round() as i32;
+        for glyph in glyphs {
+            for (dy, row) in glyph.iter().enumerate() {
+                for (dx, &f) in row.iter().enumerate() {
+                    if f {
+                        let (px, py) = (left + dx as i32 * scale, top + dy as i32 * scale);
+                        self.fill_rect(px, py, scale, scale, color);
+                    }
+                }
+            }
+            left += (glyph[0].len() as i32 + 1) * scale;
+        }
+    }
+
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut encoder = png::Encoder::new(&mut bytes, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let data = self.pixels.iter().flatten().copied().collect::<Vec<_>>();
+        // Vec への書き込みは失敗しない
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&data).unwrap();
+        writer.finish().unwrap();
+        bytes
+    }
+}
+
+/// `vis_grid_inner` と同じ絵を画素で描く。解答の画像に使う
+pub fn raster_grid_inner(
+    h: usize,
+    w: usize,
+    board: &[Vec<Option<bool>>],
+    options: &RenderOptions,
+) -> Canvas {
+    let d = options.cell_size;
+    let palette = &options.palette;
+    let mut canvas = Canvas::new(d as usize * w, d as usize * h, to_rgb(&palette.background));
+    let fill = to_rgb(&palette.fill);
+    for (y, row) in board.iter().enumerate() {
+        for (x, &val) in row.iter().enumerate() {
+            if val == Some(true) {
+                canvas.fill_rect(x as i32 * d, y as i32 * d, d, d, fill);
+            }
+        }
+    }
+    canvas
+}
+
+/// `vis_board_inner` と同じ絵を画素で描く。
+/// `board` をすべて `None` にすれば手がかりだけの問題の画像になる
+pub fn raster_board_inner(
+    h: usize,
+    w: usize,
+    board: &[Vec<Option<bool>>],
+    hints: &(Vec<Vec<i32>>, Vec<Vec<i32>>),
+    givens: &[Vec<bool>],
+    layout: &Layout,
+    options: &RenderOptions,
+) -> Canvas {
+    let d = layout.cell_size;
+    let (offset_y, offset_x) = (layout.origin_y, layout.origin_x);
+    let palette = &options.palette;
+    let mut canvas = Canvas::new(
+        layout.width as usize,
+        layout.height as usize,
+        to_rgb(&palette.background),
+    );
+
+    // grids
+    for y in 0..h {
+        for x in 0..w {
+            let color = to_rgb(cell_fill(palette, givens[y][x], board[y][x]));
+            let (px, py) = (x as i32 * d + offset_x, y as i32 * d + offset_y);
+            canvas.fill_rect(px, py, d, d, color);
+        }
+    }
+    let (left, top) = (offset_x as f64, offset_y as f64);
+    let (right, bottom) = (left + (w as i32 * d) as f64, top + (h as i32 * d) as f64);
+    let grid = to_rgb(&palette.grid);
+    for y in 0..=h {
+        let py = top + (y as i32 * d) as f64;
+        canvas.line((left, py), (right, py), options.grid_width, grid);
+    }
+    for x in 0..=w {
+        let px = left + (x as i32 * d) as f64;
+        canvas.line((px, top), (px, bottom), options.grid_width, grid);
+    }
+
+    // crosses
+    let cross = to_rgb(&palette.cross);
+    for (y, row) in board.iter().enumerate() {
+        for (x, &val) in row.iter().enumerate() {
+            if val == Some(false) {
+                let px = left + (x as i32 * d) as f64;
+                let py = top + (y as i32 * d) as f64;
+                let d = d as f64;
+                canvas.line((px, py), (px + d, py + d), options.cross_width, cross);
+                canvas.line((px + d, py), (px, py + d), options.cross_width, cross);
+            }
+        }
+    }
+
+    // frames
+    let interval = options.major_interval;
+    let frame = to_rgb(&palette.frame);
+    for y in (0..h).step_by(interval) {
+        for x in (0..w).step_by(interval) {
+            let px = left + (x as i32 * d) as f64;
+            let py = top + (y as i32 * d) as f64;
+            let size = (d * interval as i32) as f64;
+            canvas.stroke_rect(px, py, size, size, options.frame_width, frame);
+        }
+    }
+
+    // hints
+    let clue_fills = clue_fills(h, w, board, hints, options);
+    for (y, hints) in hints.0.iter().enumerate() {
+        for (i, num) in hints.iter().enumerate() {
+            let clue = &layout.rows[y][i];
+            let color = to_rgb(clue_fills.0[y][i]);
+            canvas.text(clue.x, clue.y, &num.to_string(), clue.font_size, color);
+        }
+    }
+    for (x, hints) in hints.1.iter().enumerate() {
+        for (i, num) in hints.iter().enumerate() {
+            let clue = &layout.columns[x][i];
+            let color = to_rgb(clue_fills.1[x][i]);
+            canvas.text(clue.x, clue.y, &num.to_string(), clue.font_size, color);
+        }
+    }
+    canvas
+}
+
+/// カーソルの不透明度。`vis_cursor_inner` の強調と同じ
+pub const CURSOR_ALPHA: f64 = 0.2;
+
+/// `vis_cursor_inner` と同じカーソルと行・列の強調を重ねる
+pub fn raster_cursor(
+    canvas: &mut Canvas,
+    h: usize,
+    w: usize,
+    (y, x): (usize, usize),
+    layout: &Layout,
+    options: &RenderOptions,
+) {
+    let d = layout.cell_size;
+    let (offset_y, offset_x) = (layout.origin_y, layout.origin_x);
+    let cursor = to_rgb(&options.palette.cursor);
+    let (px, py) = (x as i32 * d + offset_x, y as i32 * d + offset_y);
+    canvas.blend_rect(0, py, d * w as i32 + offset_x, d, cursor, CURSOR_ALPHA);
+    canvas.blend_rect(px, 0, d, d * h as i32 + offset_y, cursor, CURSOR_ALPHA);
+    let (px, py, d) = (px as f64, py as f64, d as f64);
+    canvas.stroke_rect(px, py, d, d, options.cursor_width, cursor);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pixel(canvas: &Canvas, x: usize, y: usize) -> Rgb {
+        canvas.pixels[y * canvas.width + x]
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#7BC96F"), Some([0x7b, 0xc9, 0x6f]));
+        assert_eq!(parse_color("#fff"), Some([255, 255, 255]));
+        assert_eq!(parse_color("LightGray"), Some([211, 211, 211]));
+        assert_eq!(parse_color("#12345"), None);
+        assert_eq!(parse_color("#fff8"), Some([255, 255, 255]));
+        assert_eq!(parse_color("#7BC96F80"), Some([0x7b, 0xc9, 0x6f]));
+        assert_eq!(parse_color("rebeccapurple"), Some([0x66, 0x33, 0x99]));
+        assert_eq!(parse_color("DarkSlateGrey"), Some([0x2f, 0x4f, 0x4f]));
+        assert_eq!(parse_color("rgb(255, 128, 0)"), Some([255, 128, 0]));
+        assert_eq!(parse_color("RGBA(10,20,30,0.5)"), Some([10, 20, 30]));
+        assert_eq!(parse_color("rgb(100% 0% 50% / 0.5)"), Some([255, 0, 128]));
+        assert_eq!(parse_color("rgb(1, 2)"), None);
+        assert_eq!(parse_color("rgb(1, 2, 3, 4, 5)"), None);
+        assert_eq!(parse_color("blurple"), None);
+        assert_eq!(to_rgb("blurple"), [0, 0, 0]);
+        assert_eq!(blend([0, 0, 0], [255, 255, 255], 0.2), [51, 51, 51]);
+    }
+
+    #[test]
+    fn test_raster_board() {
+        let board = vec![vec![Some(true), Some(false)], vec![None, None]];
+        let hints = (vec![vec![1], vec![1]], vec![vec![1], vec![1]]);
+        let givens = vec![vec![false; 2]; 2];
+        let options = RenderOptions::default();
+        let layout = Layout::new(2, 2, &hints, &options);
+        let canvas = raster_board_inner(2, 2, &board, &hints, &givens, &layout, &options);
+        assert_eq!(
+            (canvas.width, canvas.height),
+            (layout.width as usize, layout.height as usize)
+        );
+        let (ox, oy, d) = (
+            layout.origin_x as usize,
+            layout.origin_y as usize,
+            layout.cell_size as usize,
+        );
+        // 塗ったマスの中は黒、空いているマスの中は白
+        assert_eq!(pixel(&canvas, ox + d / 2, oy + d / 2), [0, 0, 0]);
+        assert_eq!(
+            pixel(&canvas, ox + d / 2 + 2, oy + d + d / 2),
+            [255, 255, 255]
+        );
+        // バツの中心
+        assert_eq!(pixel(&canvas, ox + d + d / 2, oy + d / 2), [0, 0, 0]);
+        // 手がかりの数字が描かれている
+        let clue = &layout.rows[1][0];
+        let (cx, cy) = (clue.x as usize, clue.y as usize);
+        assert!(
+            (cy - 8..cy + 8).any(|y| (cx - 8..cx + 8).any(|x| pixel(&canvas, x, y) != [255; 3]))
+        );
+
+        let png = canvas.to_png();
+        assert_eq!(&png[1..4], b"PNG");
+        let image = crate::image::Image::decode(&png).unwrap();
+        assert_eq!((image.width, image.height), (canvas.width, canvas.height));
+    }
+
+    #[test]
+    fn test_raster_grid() {
+        let board = vec![vec![Some(true), None]];
+        let options = RenderOptions {
+            cell_size: 3,
+            ..Default::default()
+        };
+        let canvas = raster_grid_inner(1, 2, &board, &options);
+        assert_eq!((canvas.width, canvas.height), (6, 3));
+        assert_eq!(pixel(&canvas, 2, 2), [0, 0, 0]);
+        assert_eq!(pixel(&canvas, 3, 0), [255, 255, 255]);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::gen::{gen_inner, GenOptions, Hints, Solver};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    /// 列を調べ直した回数をマスの個数で割ったものから決める
+    fn from_steps(h: usize, w: usize, steps: usize) -> Self {
+        Self::from_ratio(steps as f64 / (h * w) as f64, [EASY_MAX, MEDIUM_MAX])
+    }
+
+    /// 列を調べ直した回数 / マスの個数 を `[easy の上限, medium の上限]` で分ける
+    pub fn from_ratio(ratio: f64, [easy_max, medium_max]: [f64; 2]) -> Self {
+        if ratio < easy_max {
+            Difficulty::Easy
+        } else if ratio < medium_max {
+            Difficulty::Medium
+        } else {
+            Difficulty::Hard
+        }
+    }
+}
+
+// 乱択した 10x10 の盤面がおおよそ 3 等分されるように決めた。
+// 大きい盤面ほど hard が多くなる (20x20 では半分ほど)。`test_calibration` で確かめる
+const EASY_MAX: f64 = 0.6;
+const MEDIUM_MAX: f64 = 0.72;
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difficulty::Easy => write!(f, "easy"),
+            Difficulty::Medium => write!(f, "medium"),
+            Difficulty::Hard => write!(f, "hard"),
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!("unknown difficulty: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stats {
+    /// 塗るマスの割合
+    pub density: f64,
+    pub difficulty: Difficulty,
+    /// 最も長い手がかりの数字
+    pub max_clue: usize,
+    /// 1 列あたりの手がかりの個数の最大
+    pub max_clues_per_line: usize,
+    /// 手がかりの個数の合計
+    pub clues: usize,
+    pub steps: usize,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "density={:.3} difficulty={} max_clue={} max_clues_per_line={} clues={} steps={}",
+            self.density,
+            self.difficulty,
+            self.max_clue,
+            self.max_clues_per_line,
+            self.clues,
+            self.steps
+        )
+    }
+}
+
+pub fn stats(h: usize, w: usize, grid: &[bool], hints: &Hints) -> Stats {
+    let mut solver = Solver::new(h, w, hints.clone());
+    solver.solve();
+    let lines = || hints.0.iter().chain(hints.1.iter());
+    Stats {
+        density: grid.iter().filter(|&&f| f).count() as f64 / (h * w) as f64,
+        difficulty: Difficulty::from_steps(h, w, solver.steps),
+        max_clue: lines().flatten().copied().max().unwrap_or(0),
+        max_clues_per_line: lines().map(|hints| hints.len()).max().unwrap_or(0),
+        clues: lines().map(|hints| hints.len()).sum(),
+        steps: solver.steps,
+    }
+}
+
+/// 探したいパズルの条件。`None` の項目は問わない
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    pub min_density: Option<f64>,
+    pub max_density: Option<f64>,
+    pub difficulty: Option<Difficulty>,
+    /// これより長い手がかりを含まない
+    pub max_clue: Option<usize>,
+    pub max_clues_per_line: Option<usize>,
+}
+
+impl Filter {
+    pub fn matches(&self, stats: &Stats) -> bool {
+        self.min_density.is_none_or(|d| stats.density >= d)
+            && self.max_density.is_none_or(|d| stats.density <= d)
+            && self.difficulty.is_none_or(|d| stats.difficulty == d)
+            && self.max_clue.is_none_or(|c| stats.max_clue <= c)
+            && self
+                .max_clues_per_line
+                .is_none_or(|c| stats.max_clues_per_line <= c)
+    }
+}
+
+fn search_range(
+    h: usize,
+    w: usize,
+    options: &GenOptions,
+    filter: &Filter,
+    seeds: Range<u64>,
+) -> Vec<(u64, Stats)> {
+    seeds
+        .filter_map(|seed| {
+            let (grid, hints) = gen_inner(h, w, seed, options).ok()?;
+            let stats = stats(h, w, &grid, &hints);
+            if filter.matches(&stats) {
+                Some((seed, stats))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// `seeds` の範囲から条件に合う seed を小さい順に返す。
+/// ネイティブでは範囲を分けて並列に調べる。続きは `seeds.end` から探せばよい。
+pub fn search_inner(
+    h: usize,
+    w: usize,
+    options: &GenOptions,
+    filter: &Filter,
+    seeds: Range<u64>,
+) -> Vec<(u64, Stats)> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        search_range(h, w, options, filter, seeds)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get()) as u64;
+        let chunk = seeds
+            .end
+            .saturating_sub(seeds.start)
+            .div_ceil(threads)
+            .max(1);
+        std::thread::scope(|scope| {
+            let handles = (seeds.start..seeds.end)
+                .step_by(chunk as usize)
+                .map(|start| {
+                    let end = (start + chunk).min(seeds.end);
+                    scope.spawn(move || search_range(h, w, options, filter, start..end))
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let filter = Filter {
+            min_density: Some(0.5),
+            max_clue: Some(4),
+            ..Default::default()
+        };
+        let found = search_inner(10, 10, &GenOptions::default(), &filter, 0..40);
+        assert!(found.windows(2).all(|w| w[0].0 < w[1].0));
+        for (seed, stats) in &found {
+            assert!(filter.matches(stats));
+            let (grid, hints) = gen_inner(10, 10, *seed, &GenOptions::default()).unwrap();
+            assert_eq!(&super::stats(10, 10, &grid, &hints), stats);
+        }
+        let sequential = search_range(10, 10, &GenOptions::default(), &filter, 0..40);
+        assert_eq!(found, sequential);
+    }
+
+    #[test]
+    fn test_calibration() {
+        // 10x10 ではどの難易度も 1/5 以上になる
+        let n = 10;
+        let mut counts = [0; 3];
+        for seed in 0..60 {
+            let (grid, hints) = gen_inner(n, n, seed, &GenOptions::default()).unwrap();
+            counts[stats(n, n, &grid, &hints).difficulty as usize] += 1;
+        }
+        assert!(counts.iter().all(|&c| c >= 12), "{:?}", counts);
+    }
+}
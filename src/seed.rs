@@ -0,0 +1,168 @@
+use crate::gen::{gen_inner, GenError, GenOptions, Hints};
+use std::fmt;
+use std::str::FromStr;
+
+/// 新しく作る seed に付ける生成器のバージョン。
+/// 盤面の出力が変わる変更は、今のバージョンを写して残したうえで新しいバージョンとして足す
+pub const LATEST: u32 = 1;
+
+/// 生成器のバージョン付きの seed。`v1-12345` のように書く。
+/// バージョンのない数字だけの seed は v1 とみなす
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeedCode {
+    pub version: u32,
+    pub seed: u64,
+}
+
+/// 知らないバージョンは読めるが、`generate` で `GenError::UnknownVersion` になる
+#[derive(Debug, PartialEq, Eq)]
+pub enum SeedError {
+    Empty,
+}
+
+impl fmt::Display for SeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeedError::Empty => write!(f, "seed is empty"),
+        }
+    }
+}
+
+/// FNV-1a (64 bit)。文字列の seed を数に直すのに使うので、結果を変えてはいけない
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl SeedCode {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            version: LATEST,
+            seed,
+        }
+    }
+
+    /// seed が作られたときのバージョンの生成器で盤面を作る
+    pub fn generate(
+        &self,
+        h: usize,
+        w: usize,
+        options: &GenOptions,
+    ) -> Result<(Vec<bool>, Hints), GenError> {
+        match self.version {
+            1 => gen_v1(h, w, self.seed, options),
+            v => Err(GenError::UnknownVersion(v)),
+        }
+    }
+}
+
+impl FromStr for SeedCode {
+    type Err = SeedError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(SeedError::Empty);
+        }
+        // `v<数字>-` で始まればバージョン付き
+        let versioned = s
+            .strip_prefix('v')
+            .and_then(|rest| rest.split_once('-'))
+            .and_then(|(version, body)| Some((version.parse::<u32>().ok()?, body)));
+        let (version, body) = versioned.unwrap_or((1, s));
+        // 数でなければ文字列の seed としてハッシュする
+        let seed = body.parse().unwrap_or_else(|_| fnv1a(body));
+        Ok(Self { version, seed })
+    }
+}
+
+impl fmt::Display for SeedCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}-{}", self.version, self.seed)
+    }
+}
+
+/// v1 の生成器。v1 の seed は共有リンクに残っているので、出力は二度と変えてはいけない。
+/// 今は `gen_inner` をそのまま呼ぶので、`gen_inner` (と、その使う乱数や判定) も v1 と同じ出力を保つこと。
+/// 保てない変更をするときは、変わる前の `gen_inner` をこのファイルに写して v1 をそれに固定し、
+/// 新しい出力は `LATEST` を上げた v2 にする。`test_golden_v1` が出力の変化を検出する
+fn gen_v1(
+    h: usize,
+    w: usize,
+    seed: u64,
+    options: &GenOptions,
+) -> Result<(Vec<bool>, Hints), GenError> {
+    gen_inner(h, w, seed, options)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::gen::Symmetry;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("v1-42".parse(), Ok(SeedCode::new(42)));
+        assert_eq!("42".parse(), Ok(SeedCode::new(42)));
+        assert_eq!(SeedCode::new(42).to_string(), "v1-42");
+        assert_eq!("v1-a".parse(), Ok(SeedCode::new(0xaf63dc4c8601ec8c)));
+        assert_eq!("a".parse::<SeedCode>(), "v1-a".parse());
+        assert_eq!("gyoza".parse::<SeedCode>(), " gyoza ".parse());
+        assert_eq!("".parse::<SeedCode>(), Err(SeedError::Empty));
+        let code = "v9-42".parse::<SeedCode>().unwrap();
+        assert_eq!(
+            code,
+            SeedCode {
+                version: 9,
+                seed: 42,
+            }
+        );
+        assert_eq!(
+            code.generate(5, 5, &GenOptions::default()),
+            Err(GenError::UnknownVersion(9))
+        );
+    }
+
+    #[test]
+    fn test_golden_v1() {
+        // 共有リンクの盤面が変わらないように、v1 の出力を固定する
+        let golden = [
+            (5, 1, "0100110111001111010001101"),
+            (5, 42, "0111110111100001101111000"),
+            (5, 123456789, "0100010000011111010011010"),
+            (
+                10,
+                0,
+                "1001011110111000111000001010110011111010001000110001\
+                 000110111110000011001010001000101111110010110110",
+            ),
+            (
+                10,
+                42,
+                "1110001111101001110100101110101000100101011011111010\
+                 110111011010001111101001001000111110110000110111",
+            ),
+        ];
+        for (n, seed, expected) in golden {
+            let code = format!("v1-{}", seed).parse::<SeedCode>().unwrap();
+            let (grid, _) = code.generate(n, n, &GenOptions::default()).unwrap();
+            let grid = grid
+                .iter()
+                .map(|&f| if f { '1' } else { '0' })
+                .collect::<String>();
+            assert_eq!(grid, expected, "{}x{} seed = {}", n, n, seed);
+        }
+
+        // 対称性の付いた盤面も固定する
+        let options = GenOptions {
+            symmetry: Symmetry::Rotational,
+            ..GenOptions::default()
+        };
+        let (grid, _) = SeedCode::new(7).generate(6, 6, &options).unwrap();
+        let grid = grid
+            .iter()
+            .map(|&f| if f { '1' } else { '0' })
+            .collect::<String>();
+        assert_eq!(grid, "011110010101111100001111101010011110");
+    }
+}
@@ -0,0 +1,266 @@
+use crate::gen::{Board, Hints, Solver};
+use itertools::Itertools;
+use rand::prelude::*;
+
+/// 小さなドット絵。`#` が塗るマス。
+type Icon = &'static [&'static str];
+
+#[rustfmt::skip]
+pub const ICONS: &[Icon] = &[
+    // 魚
+    &[
+        "..####..#",
+        ".######.#",
+        "#.#######",
+        ".######.#",
+        "..####..#",
+    ],
+    // 餃子
+    &[
+        "..#.#.#..",
+        ".#######.",
+        "#########",
+        "#########",
+        ".#######.",
+    ],
+    // 寿司
+    &[
+        ".######.",
+        "########",
+        "#......#",
+        ".######.",
+    ],
+    // おにぎり
+    &[
+        "...#...",
+        "..#.#..",
+        ".#...#.",
+        ".#...#.",
+        "#######",
+        "#######",
+        "#######",
+    ],
+    // 星
+    &[
+        "...#...",
+        "...#...",
+        "#######",
+        ".#####.",
+        "..###..",
+        ".##.##.",
+        "##...##",
+    ],
+    // ハート
+    &[
+        ".##.##.",
+        "#######",
+        "#######",
+        ".#####.",
+        "..###..",
+        "...#...",
+    ],
+    // 猫
+    &[
+        "#.....#",
+        "##...##",
+        "#######",
+        "#.###.#",
+        "#######",
+        ".#####.",
+    ],
+];
+
+pub enum Shape {
+    Circle { cy: i32, cx: i32, r: i32 },
+    Rect { y: i32, x: i32, h: i32, w: i32 },
+    Line { y0: i32, x0: i32, y1: i32, x1: i32 },
+    Triangle { points: [(i32, i32); 3] },
+    Icon { icon: Icon, y: i32, x: i32 },
+}
+
+impl Shape {
+    /// 図形が覆うマス（盤外は除く）
+    pub fn cells(&self, h: usize, w: usize) -> Vec<(usize, usize)> {
+        let cells = match *self {
+            Shape::Circle { cy, cx, r } => (cy - r..=cy + r)
+                .cartesian_product(cx - r..=cx + r)
+                .filter(|&(y, x)| (y - cy).pow(2) + (x - cx).pow(2) <= r * r + r)
+                .collect_vec(),
+            Shape::Rect { y, x, h, w } => (y..y + h).cartesian_product(x..x + w).collect_vec(),
+            Shape::Line { y0, x0, y1, x1 } => {
+                // Bresenham
+                let (dy, dx) = (-(y1 - y0).abs(), (x1 - x0).abs());
+                let (sy, sx) = ((y1 - y0).signum(), (x1 - x0).signum());
+                let (mut y, mut x, mut err) = (y0, x0, dx + dy);
+                let mut cells = vec![(y, x)];
+                while (y, x) != (y1, x1) {
+                    let e2 = 2 * err;
+                    if e2 >= dy {
+                        err += dy;
+                        x += sx;
+                    }
+                    if e2 <= dx {
+                        err += dx;
+                        y += sy;
+                    }
+                    cells.push((y, x));
+                }
+                cells
+            }
+            Shape::Triangle { points } => {
+                let (ys, xs) = (points.map(|p| p.0), points.map(|p| p.1));
+                let cross = |(ay, ax): (i32, i32), (by, bx): (i32, i32), (py, px): (i32, i32)| {
+                    // マスの中心で判定するため座標を 2 倍する
+                    (2 * bx - 2 * ax) * (py - 2 * ay) - (2 * by - 2 * ay) * (px - 2 * ax)
+                };
+                (*ys.iter().min().unwrap()..=*ys.iter().max().unwrap())
+                    .cartesian_product(*xs.iter().min().unwrap()..=*xs.iter().max().unwrap())
+                    .filter(|&(y, x)| {
+                        let p = (2 * y + 1, 2 * x + 1);
+                        let d = [
+                            cross(points[0], points[1], p),
+                            cross(points[1], points[2], p),
+                            cross(points[2], points[0], p),
+                        ];
+                        d.iter().all(|&d| d >= 0) || d.iter().all(|&d| d <= 0)
+                    })
+                    .collect_vec()
+            }
+            Shape::Icon { icon, y, x } => icon
+                .iter()
+                .enumerate()
+                .flat_map(|(dy, row)| {
+                    row.bytes()
+                        .enumerate()
+                        .filter(|&(_, c)| c == b'#')
+                        .map(move |(dx, _)| (y + dy as i32, x + dx as i32))
+                })
+                .collect_vec(),
+        };
+        cells
+            .into_iter()
+            .filter(|&(y, x)| 0 <= y && y < h as i32 && 0 <= x && x < w as i32)
+            .map(|(y, x)| (y as usize, x as usize))
+            .collect_vec()
+    }
+
+    pub fn random<R: Rng>(h: usize, w: usize, rng: &mut R) -> Self {
+        let (h, w) = (h as i32, w as i32);
+        let n = h.min(w);
+        loop {
+            return match rng.gen_range(0..5) {
+                0 => Shape::Circle {
+                    cy: rng.gen_range(0..h),
+                    cx: rng.gen_range(0..w),
+                    r: rng.gen_range((n / 6).max(1)..=(n / 3).max(1)),
+                },
+                1 => Shape::Rect {
+                    y: rng.gen_range(0..h),
+                    x: rng.gen_range(0..w),
+                    h: rng.gen_range((n / 4).max(1)..=(n / 2).max(1)),
+                    w: rng.gen_range((n / 4).max(1)..=(n / 2).max(1)),
+                },
+                2 => Shape::Line {
+                    y0: rng.gen_range(0..h),
+                    x0: rng.gen_range(0..w),
+                    y1: rng.gen_range(0..h),
+                    x1: rng.gen_range(0..w),
+                },
+                3 => Shape::Triangle {
+                    points: [(); 3].map(|_| (rng.gen_range(0..h), rng.gen_range(0..w))),
+                },
+                _ => {
+                    let icon = *ICONS.choose(rng).unwrap();
+                    let (ih, iw) = (icon.len() as i32, icon[0].len() as i32);
+                    if ih > h || iw > w {
+                        continue;
+                    }
+                    Shape::Icon {
+                        icon,
+                        y: rng.gen_range(0..=h - ih),
+                        x: rng.gen_range(0..=w - iw),
+                    }
+                }
+            };
+        }
+    }
+}
+
+fn render(h: usize, w: usize, shapes: &[Shape]) -> Board {
+    let mut grid = vec![vec![Some(false); w]; h];
+    for shape in shapes {
+        for (y, x) in shape.cells(h, w) {
+            grid[y][x] = Some(true);
+        }
+    }
+    Board::new(h, w, grid)
+}
+
+/// 図形を並べた絵から一意に解けるパズルを作る
+pub fn compose_inner(h: usize, w: usize, seed: u64) -> (Vec<bool>, Hints) {
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(seed);
+    loop {
+        let mut shapes = (0..rng.gen_range(2..=4))
+            .map(|_| Shape::random(h, w, &mut rng))
+            .collect_vec();
+        for _ in 0..30 {
+            let mut board = render(h, w, &shapes);
+            let hints = board.get_hints();
+            if hints.0.iter().all(|hint| hint.is_empty()) {
+                break;
+            }
+            let mut solver = Solver::new(h, w, hints.clone());
+            if solver.solve() {
+                return (solver.board.get_plain(), hints);
+            }
+
+            // 決まらなかったマスに重なっている図形を置き直す
+            let ambiguous = |(y, x): (usize, usize)| solver.board.grid[y][x].is_none();
+            let cells = shapes.iter().map(|shape| shape.cells(h, w)).collect_vec();
+            let overlapping = (0..shapes.len())
+                .filter(|&i| {
+                    cells[i].iter().any(|&cell| ambiguous(cell))
+                        && (0..shapes.len())
+                            .any(|j| j != i && cells[i].iter().any(|cell| cells[j].contains(cell)))
+                })
+                .collect_vec();
+            if let Some(&i) = overlapping.choose(&mut rng) {
+                shapes[i] = Shape::random(h, w, &mut rng);
+            } else {
+                // 重なりが原因でなければ、決まらなかったマスを 1 つ塗って曖昧さを消す
+                let (y, x) = (0..h)
+                    .cartesian_product(0..w)
+                    .find(|&cell| ambiguous(cell))
+                    .unwrap();
+                shapes.push(Shape::Rect {
+                    y: y as i32,
+                    x: x as i32,
+                    h: 1,
+                    w: 1,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_icons_are_rectangular() {
+        for icon in ICONS {
+            assert!(icon.iter().all(|row| row.len() == icon[0].len()));
+        }
+    }
+
+    #[test]
+    fn test_compose() {
+        let (h, w) = (15, 10);
+        let (grid, hints) = compose_inner(h, w, 0);
+        let mut solver = Solver::new(h, w, hints);
+        assert!(solver.solve());
+        assert_eq!(solver.board.get_plain(), grid);
+        assert_eq!(grid, compose_inner(h, w, 0).0);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::font;
+use crate::gen::Puzzle;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TextError {
+    /// フォントにない文字
+    UnknownChar(char),
+    /// 折り返しても盤面に収まらない
+    TooLarge,
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextError::UnknownChar(c) => write!(f, "unknown character: {:?}", c),
+            TextError::TooLarge => write!(f, "text does not fit on the board"),
+        }
+    }
+}
+
+pub struct TextPuzzle {
+    pub grid: Vec<Vec<bool>>,
+    pub puzzle: Puzzle,
+}
+
+/// 1 行ぶんの文字を 1 マス空けて並べる
+fn render_line(text: &str) -> Result<Vec<Vec<bool>>, TextError> {
+    let mut line = vec![vec![]; font::HEIGHT];
+    for (i, c) in text.chars().enumerate() {
+        let glyph = font::glyph(c).ok_or(TextError::UnknownChar(c))?;
+        for (row, glyph) in line.iter_mut().zip(glyph) {
+            if i > 0 {
+                row.push(false);
+            }
+            row.extend(glyph);
+        }
+    }
+    Ok(line)
+}
+
+/// 幅 w に収まるように折り返す。単語の途中でも収まらなければ文字単位で折り返す
+fn wrap(text: &str, w: usize) -> Result<Vec<Vec<Vec<bool>>>, TextError> {
+    let fits = |line: &str| -> Result<bool, TextError> { Ok(render_line(line)?[0].len() <= w) };
+    let mut lines = Vec::<String>::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let joined = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", current, word)
+        };
+        if fits(&joined)? {
+            current = joined;
+            continue;
+        }
+        if !current.is_empty() {
+            lines.push(current);
+        }
+        current = String::new();
+        for c in word.chars() {
+            let joined = format!("{}{}", current, c);
+            if fits(&joined)? {
+                current = joined;
+            } else if current.is_empty() {
+                return Err(TextError::TooLarge);
+            } else {
+                lines.push(current);
+                current = c.to_string();
+            }
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines.iter().map(|line| render_line(line)).collect()
+}
+
+/// 文字列を h x w の盤面の中央に並べる。1 行に収まらなければ折り返す
+pub fn layout_text(h: usize, w: usize, text: &str) -> Result<Vec<Vec<bool>>, TextError> {
+    let single = render_line(text.trim())?;
+    let lines = if single[0].len() <= w {
+        vec![single]
+    } else {
+        wrap(text, w)?
+    };
+    let height = lines.len() * (font::HEIGHT + 1) - 1;
+    if height > h {
+        return Err(TextError::TooLarge);
+    }
+
+    let mut grid = vec![vec![false; w]; h];
+    let top = (h - height) / 2;
+    for (i, line) in lines.iter().enumerate() {
+        let left = (w - line[0].len()) / 2;
+        for (dy, row) in line.iter().enumerate() {
+            for (dx, &f) in row.iter().enumerate() {
+                grid[top + i * (font::HEIGHT + 1) + dy][left + dx] = f;
+            }
+        }
+    }
+    Ok(grid)
+}
+
+pub fn text_puzzle_inner(
+    h: usize,
+    w: usize,
+    text: &str,
+    with_givens: bool,
+) -> Result<TextPuzzle, TextError> {
+    let grid = layout_text(h, w, text)?;
+    let puzzle = Puzzle::from_grid(&grid, with_givens);
+    Ok(TextPuzzle { grid, puzzle })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::gen::Solver;
+    use itertools::Itertools;
+
+    #[test]
+    fn test_layout_text() {
+        let grid = layout_text(9, 11, "hi").unwrap();
+        let text = grid
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&f| if f { '#' } else { '.' })
+                    .collect::<String>()
+            })
+            .collect_vec();
+        assert_eq!(
+            text,
+            [
+                "...........",
+                "#...#..###.",
+                "#...#...#..",
+                "#...#...#..",
+                "#####...#..",
+                "#...#...#..",
+                "#...#...#..",
+                "#...#..###.",
+                "...........",
+            ]
+        );
+        assert_eq!(layout_text(16, 30, "GYOZA SUSHI").unwrap().len(), 16);
+        assert_eq!(layout_text(7, 10, "GYOZA"), Err(TextError::TooLarge));
+        assert_eq!(
+            layout_text(9, 30, "餃子"),
+            Err(TextError::UnknownChar('餃'))
+        );
+    }
+
+    #[test]
+    fn test_text_puzzle() {
+        let (h, w) = (10, 35);
+        let TextPuzzle { grid, puzzle } = text_puzzle_inner(h, w, "ギョーザ", true).unwrap();
+        let mut solver = Solver::with_givens(h, w, puzzle.hints, &puzzle.givens);
+        assert!(solver.solve());
+        assert_eq!(solver.board.get_plain(), grid.concat());
+    }
+}
@@ -0,0 +1,184 @@
+use crate::gen::runs;
+use crate::raster::to_rgb;
+use crate::vis::RenderOptions;
+use itertools::Itertools;
+use std::fmt::Write;
+
+/// 1 pt あたりの cm
+const CM_PER_PT: f64 = 2.54 / 72.27;
+/// 手がかりと盤面の間 (マス)
+const CLUE_GAP: f64 = 0.25;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TikzOptions {
+    /// 1 マスの一辺 (cm)
+    pub cell_size: f64,
+    /// `\documentclass{standalone}` で囲んで、そのままコンパイルできるようにする
+    pub document: bool,
+}
+
+impl Default for TikzOptions {
+    fn default() -> Self {
+        Self {
+            cell_size: 0.5,
+            document: false,
+        }
+    }
+}
+
+fn define_color(ret: &mut String, name: &str, color: &str) {
+    let [r, g, b] = to_rgb(color);
+    writeln!(
+        ret,
+        "\\definecolor{{{}}}{{HTML}}{{{:02X}{:02X}{:02X}}}",
+        name, r, g, b
+    )
+    .unwrap();
+}
+
+/// `vis_board_inner` と同じ見た目の TikZ の図。`solution` があれば塗ったマスも描く。
+/// 線の太さやフォントの大きさは `options` の値をマスの大きさとの比で写す。
+/// `tikz` と `xcolor` のパッケージが必要
+pub fn tikz_inner(
+    h: usize,
+    w: usize,
+    hints: &(Vec<Vec<i32>>, Vec<Vec<i32>>),
+    solution: Option<&[Vec<bool>]>,
+    tikz: &TikzOptions,
+    options: &RenderOptions,
+) -> String {
+    let palette = &options.palette;
+    // SVG の 1 px が何 pt になるか
+    let pt = tikz.cell_size / CM_PER_PT / options.cell_size as f64;
+    let font_size = options
+        .font_size
+        .unwrap_or(options.cell_size as f64 * 2.0 / 3.0)
+        * pt;
+
+    let mut ret = String::new();
+    if tikz.document {
+        ret.push_str("\\documentclass[tikz]{standalone}\n\\begin{document}\n");
+    }
+    define_color(&mut ret, "illufill", &palette.fill);
+    define_color(&mut ret, "illugrid", &palette.grid);
+    define_color(&mut ret, "illuframe", &palette.frame);
+    define_color(&mut ret, "illuclue", &palette.clue);
+    // y 軸を下向きにして、SVG と同じく左上を原点にする
+    writeln!(
+        ret,
+        "\\begin{{tikzpicture}}[x={}cm, y=-{}cm, \
+         clue/.style={{text=illuclue, font=\\fontsize{{{:.1}}}{{{:.1}}}\\selectfont, inner sep=0}}]",
+        tikz.cell_size,
+        tikz.cell_size,
+        font_size,
+        font_size * 1.2
+    )
+    .unwrap();
+
+    if let Some(solution) = solution {
+        for (y, row) in solution.iter().enumerate() {
+            for (x, len) in runs(row) {
+                writeln!(
+                    ret,
+                    "\\fill[illufill] ({},{}) rectangle ++({},1);",
+                    x, y, len
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    writeln!(
+        ret,
+        "\\draw[illugrid, line width={:.2}pt] (0,0) grid ({},{});",
+        options.grid_width * pt,
+        w,
+        h
+    )
+    .unwrap();
+    // 太線は `major_interval` ごとと外枠
+    let interval = options.major_interval;
+    let frame = format!("illuframe, line width={:.2}pt", options.frame_width * pt);
+    for x in (interval..w).step_by(interval) {
+        writeln!(ret, "\\draw[{}] ({},0) -- ({},{});", frame, x, x, h).unwrap();
+    }
+    for y in (interval..h).step_by(interval) {
+        writeln!(ret, "\\draw[{}] (0,{}) -- ({},{});", frame, y, w, y).unwrap();
+    }
+    writeln!(ret, "\\draw[{}] (0,0) rectangle ({},{});", frame, w, h).unwrap();
+
+    // 横の手がかりは右寄せ、縦の手がかりは下寄せ
+    for (y, hints) in hints.0.iter().enumerate() {
+        writeln!(
+            ret,
+            "\\node[clue, anchor=east] at (-{},{}) {{{}}};",
+            CLUE_GAP,
+            y as f64 + 0.5,
+            hints.iter().join("\\enspace ")
+        )
+        .unwrap();
+    }
+    for (x, hints) in hints.1.iter().enumerate() {
+        writeln!(
+            ret,
+            "\\node[clue, anchor=south, align=center] at ({},-{}) {{{}}};",
+            x as f64 + 0.5,
+            CLUE_GAP,
+            hints.iter().join("\\\\ ")
+        )
+        .unwrap();
+    }
+
+    ret.push_str("\\end{tikzpicture}\n");
+    if tikz.document {
+        ret.push_str("\\end{document}\n");
+    }
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tikz() {
+        let hints = (vec![vec![1, 1], vec![2]], vec![vec![1], vec![1]]);
+        let solution = vec![vec![true, false], vec![true, true]];
+        let options = RenderOptions::default();
+        let tikz = TikzOptions::default();
+        let puzzle = tikz_inner(2, 2, &hints, None, &tikz, &options);
+        assert!(puzzle.starts_with("\\definecolor{illufill}{HTML}{000000}"));
+        assert!(puzzle.contains("(0,0) grid (2,2);"));
+        assert!(puzzle.contains("at (-0.25,0.5) {1\\enspace 1};"));
+        assert!(puzzle.contains("anchor=south, align=center] at (1.5,-0.25) {1};"));
+        assert!(!puzzle.contains("\\fill"));
+        assert_eq!(puzzle.matches("\\begin{tikzpicture}").count(), 1);
+
+        let tikz = TikzOptions {
+            document: true,
+            ..Default::default()
+        };
+        let answer = tikz_inner(2, 2, &hints, Some(&solution), &tikz, &options);
+        assert!(answer.starts_with("\\documentclass[tikz]{standalone}"));
+        assert!(answer.contains("\\fill[illufill] (0,0) rectangle ++(1,1);"));
+        assert!(answer.contains("\\fill[illufill] (0,1) rectangle ++(2,1);"));
+        assert!(answer.ends_with("\\end{document}\n"));
+    }
+
+    #[test]
+    fn test_tikz_frames() {
+        let hints = (vec![vec![1]; 12], vec![vec![1]; 12]);
+        let tikz = tikz_inner(
+            12,
+            12,
+            &hints,
+            None,
+            &TikzOptions::default(),
+            &RenderOptions::default(),
+        );
+        // 5, 10 マス目の太線と外枠
+        assert_eq!(tikz.matches("illuframe, line width").count(), 5);
+        assert!(tikz.contains("(5,0) -- (5,12);"));
+        assert!(tikz.contains("(0,10) -- (12,10);"));
+    }
+}
@@ -1,30 +1,233 @@
-use gif::Gif;
+use crate::gif::Gif;
+use crate::layout::Layout;
+use crate::line;
+use crate::raster::{blend, raster_board_inner, raster_cursor, to_rgb, Rgb, CURSOR_ALPHA};
 use itertools::Itertools;
 use svg::node::element::path::Data;
-use svg::node::element::{Path, Rectangle, Text};
+use svg::node::element::{Element, Group, Path, Rectangle, Text};
+use svg::node::Node;
 use svg::Document;
 
-const D: i32 = 24;
-const THEME: &str = "#7BC96F";
+/// 盤面の色
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub background: String,
+    /// 塗ったマス
+    pub fill: String,
+    pub cross: String,
+    pub grid: String,
+    /// `major_interval` ごとの太線
+    pub frame: String,
+    pub clue: String,
+    /// 満たされた手がかり
+    pub clue_satisfied: String,
+    /// 手がかりと矛盾している列の手がかり
+    pub conflict: String,
+    /// カーソルと、カーソルのある行・列の強調
+    pub cursor: String,
+    /// 最初から決まっているマス
+    pub given_fill: String,
+    pub given_background: String,
+    /// ゲーミングモードで順に切り替える塗りの色
+    pub gaming: Vec<String>,
+}
+
+fn colors(colors: &[&str]) -> Vec<String> {
+    colors.iter().map(|c| c.to_string()).collect()
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            background: "white".to_string(),
+            fill: "black".to_string(),
+            cross: "black".to_string(),
+            grid: "black".to_string(),
+            frame: "black".to_string(),
+            clue: "black".to_string(),
+            clue_satisfied: "lightgray".to_string(),
+            conflict: "red".to_string(),
+            cursor: "#7BC96F".to_string(),
+            given_fill: "dimgray".to_string(),
+            given_background: "gainsboro".to_string(),
+            gaming: colors(&[
+                "#ff0000", "#ff7f00", "#ffff00", "#7fff00", "#00ff00", "#00ff7f", "#00ffff",
+                "#007fff", "#0000ff", "#7f00ff", "#ff00ff", "#ff007f",
+            ]),
+        }
+    }
+}
+
+/// SVG の描き方。`Default` がこれまでの見た目
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderOptions {
+    /// 1 マスの一辺の長さ
+    pub cell_size: i32,
+    pub palette: Palette,
+    /// 手がかりのフォント。`None` ならブラウザの既定
+    pub font_family: Option<String>,
+    /// `None` ならマスの大きさから決める
+    pub font_size: Option<f64>,
+    pub grid_width: f64,
+    pub cross_width: f64,
+    pub frame_width: f64,
+    pub cursor_width: f64,
+    /// 太線を引く間隔 (マス)
+    pub major_interval: usize,
+    /// 手がかりと矛盾している列の手がかりを `palette.conflict` で描く
+    pub highlight_conflicts: bool,
+    /// 同じ色の横に並んだマスや罫線をまとめて 1 つの path で描く。
+    /// 見た目は同じで SVG が小さくなるが、id を付けないので差分では書き換えられない。
+    /// id が重ならないので、1 つの文書に盤面をいくつも並べられる
+    pub compact: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            cell_size: 24,
+            palette: Palette::default(),
+            font_family: None,
+            font_size: None,
+            grid_width: 1.0,
+            cross_width: 0.5,
+            frame_width: 4.0,
+            cursor_width: 4.0,
+            major_interval: 5,
+            highlight_conflicts: false,
+            compact: false,
+        }
+    }
+}
+
+/// 組み込みの配色
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    Light,
+    Dark,
+    /// 線を太くし、色の差を大きくする
+    HighContrast,
+    /// 1 型・2 型色覚でも見分けやすいように、赤と緑を避けて青と橙を使う
+    ColorBlind,
+}
+
+impl RenderOptions {
+    pub fn preset(preset: Preset) -> Self {
+        let mut options = Self::default();
+        let palette = &mut options.palette;
+        match preset {
+            Preset::Light => {}
+            Preset::Dark => {
+                *palette = Palette {
+                    background: "#1e1e1e".to_string(),
+                    fill: "#e0e0e0".to_string(),
+                    cross: "#9e9e9e".to_string(),
+                    grid: "#5a5a5a".to_string(),
+                    frame: "#c8c8c8".to_string(),
+                    clue: "#e0e0e0".to_string(),
+                    clue_satisfied: "#5a5a5a".to_string(),
+                    conflict: "#ff6e6e".to_string(),
+                    cursor: "#4fc3f7".to_string(),
+                    given_fill: "#9e9e9e".to_string(),
+                    given_background: "#333333".to_string(),
+                    gaming: colors(&[
+                        "#ff6e6e", "#ffb060", "#ffff70", "#b0ff70", "#70ff70", "#70ffb0",
+                        "#70ffff", "#70b0ff", "#8080ff", "#b070ff", "#ff70ff", "#ff70b0",
+                    ]),
+                };
+            }
+            Preset::HighContrast => {
+                palette.clue_satisfied = "#8c8c8c".to_string();
+                palette.conflict = "#c00000".to_string();
+                palette.cursor = "#0000ff".to_string();
+                palette.given_fill = "#595959".to_string();
+                palette.given_background = "#d0d0d0".to_string();
+                palette.gaming = colors(&["#000000", "#0000ff", "#8b0000", "#005000"]);
+                options.grid_width = 1.5;
+                options.cross_width = 1.5;
+                options.frame_width = 5.0;
+                options.cursor_width = 5.0;
+            }
+            // Okabe–Ito の配色から選ぶ。1 型色覚では赤が暗く見えるので、赤みの強い色は除く
+            Preset::ColorBlind => {
+                palette.cursor = "#0072b2".to_string();
+                palette.clue_satisfied = "#bbbbbb".to_string();
+                palette.conflict = "#d55e00".to_string();
+                palette.gaming = colors(&[
+                    "#0072b2", "#56b4e9", "#f0e442", "#e69f00", "#009e73", "#cc79a7",
+                ]);
+            }
+        }
+        options
+    }
 
-pub fn vis_grid_inner(h: usize, w: usize, d: i32, board: &Vec<Vec<Option<bool>>>) -> String {
+    fn clue_text(&self) -> Text {
+        let mut text = Text::new();
+        if let Some(font_family) = &self.font_family {
+            text = text.set("font-family", font_family.as_str());
+        }
+        text
+    }
+}
+
+pub fn vis_grid_inner(
+    h: usize,
+    w: usize,
+    board: &[Vec<Option<bool>>],
+    options: &RenderOptions,
+) -> String {
+    grid_document(h, w, board, options).to_string()
+}
+
+pub fn grid_document(
+    h: usize,
+    w: usize,
+    board: &[Vec<Option<bool>>],
+    options: &RenderOptions,
+) -> Document {
+    let d = options.cell_size;
+    let palette = &options.palette;
     let mut doc = Document::new()
-        .set("id", "vis")
         .set("viewBox", (0, 0, d * w as i32, d * h as i32))
         .set("width", d * w as i32)
         .set("height", d * h as i32);
+    if options.compact {
+        // 背景を 1 枚敷いて、塗ったマスだけを 1 つの path で描く
+        doc = doc.add(
+            Rectangle::new()
+                .set("fill", palette.background.as_str())
+                .set("width", d * w as i32)
+                .set("height", d * h as i32),
+        );
+        let fill = palette.fill.as_str();
+        let color = |y: usize, x: usize| {
+            if board[y][x] == Some(true) {
+                fill
+            } else {
+                ""
+            }
+        };
+        for (color, data) in cell_runs(h, w, d, (0, 0), color) {
+            if color == fill {
+                doc = doc.add(Path::new().set("fill", color).set("d", data));
+            }
+        }
+        return doc;
+    }
+    doc = doc.set("id", "vis");
     // grids
-    for y in 0..h {
-        for x in 0..w {
+    for (y, row) in board.iter().enumerate() {
+        for (x, &val) in row.iter().enumerate() {
             doc = doc.add(
                 Rectangle::new()
                     .set(
                         "fill",
-                        if board[y][x] == Some(true) {
-                            "black"
+                        if val == Some(true) {
+                            &palette.fill
                         } else {
-                            "white"
-                        },
+                            &palette.background
+                        }
+                        .as_str(),
                     )
                     .set("x", x as i32 * d)
                     .set("y", y as i32 * d)
@@ -34,100 +237,553 @@ pub fn vis_grid_inner(h: usize, w: usize, d: i32, board: &Vec<Vec<Option<bool>>>
             );
         }
     }
-    doc.to_string()
+    doc
+}
+
+/// 横に並んだ同じ色のマスをまとめた、色ごとの path。色は最初に現れた順
+fn cell_runs<'a>(
+    h: usize,
+    w: usize,
+    d: i32,
+    (offset_x, offset_y): (i32, i32),
+    color: impl Fn(usize, usize) -> &'a str,
+) -> Vec<(&'a str, Data)> {
+    let mut paths: Vec<(&str, Data)> = vec![];
+    for y in 0..h {
+        let mut x = 0;
+        while x < w {
+            let c = color(y, x);
+            let mut nx = x;
+            while nx < w && color(y, nx) == c {
+                nx += 1;
+            }
+            let len = (nx - x) as i32 * d;
+            let i = match paths.iter().position(|&(other, _)| other == c) {
+                Some(i) => i,
+                None => {
+                    paths.push((c, Data::new()));
+                    paths.len() - 1
+                }
+            };
+            let data = std::mem::take(&mut paths[i].1);
+            paths[i].1 = data
+                .move_to((x as i32 * d + offset_x, y as i32 * d + offset_y))
+                .horizontal_line_by(len)
+                .vertical_line_by(d)
+                .horizontal_line_by(-len)
+                .close();
+            x = nx;
+        }
+    }
+    paths
+}
+
+/// アニメーションの 1 コマ
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayFrame {
+    pub board: Vec<Vec<Option<bool>>>,
+    pub cursor: Option<(usize, usize)>,
+}
+
+/// GIF の色表。盤面の色と、それにカーソルの色を重ねた色
+pub fn gif_palette(options: &RenderOptions) -> Vec<Rgb> {
+    let palette = &options.palette;
+    let base = [
+        &palette.background,
+        &palette.fill,
+        &palette.cross,
+        &palette.grid,
+        &palette.frame,
+        &palette.clue,
+        &palette.clue_satisfied,
+        &palette.conflict,
+        &palette.cursor,
+        &palette.given_fill,
+        &palette.given_background,
+    ]
+    .iter()
+    .map(|color| to_rgb(color))
+    .collect_vec();
+    let cursor = to_rgb(&palette.cursor);
+    // 行と列の強調が重なるところは 2 回重ねる
+    let once = base.iter().map(|&c| blend(c, cursor, CURSOR_ALPHA));
+    let twice = once.clone().map(|c| blend(c, cursor, CURSOR_ALPHA));
+    base.iter()
+        .copied()
+        .chain(once)
+        .chain(twice)
+        .unique()
+        .collect()
+}
+
+/// GIF のコマの長さの決め方
+#[derive(Clone, Debug, PartialEq)]
+pub enum GifSpeed {
+    /// どのコマも `GifOptions::delay`
+    Fixed,
+    /// コマごとの時刻 (ミリ秒)。次のコマまでの時間をそのまま使う
+    RealTime(Vec<f64>),
+    /// 全体の長さ (1/100 秒) を均等に分ける。`hold` と `intro` は含まない
+    Total(u32),
+}
+
+/// GIF の設定。時間は 1/100 秒単位で、`Default` がこれまでの GIF
+#[derive(Clone, Debug, PartialEq)]
+pub struct GifOptions {
+    pub delay: u16,
+    pub speed: GifSpeed,
+    /// 再生する回数。`None` なら繰り返し続ける
+    pub loop_count: Option<u16>,
+    /// 最後のコマ (完成した盤面) を止める時間
+    pub hold: Option<u16>,
+    /// 最初に何も塗っていない問題を見せる時間
+    pub intro: Option<u16>,
+}
+
+impl Default for GifOptions {
+    fn default() -> Self {
+        Self {
+            delay: 20,
+            speed: GifSpeed::Fixed,
+            loop_count: None,
+            hold: Some(200),
+            intro: None,
+        }
+    }
+}
+
+/// 多くのブラウザは 2 未満の待ち時間を 10 として扱うので、それより短くしない
+const MIN_DELAY: u16 = 2;
+
+impl GifOptions {
+    /// `n` コマそれぞれの長さ。`intro` のコマは含まない
+    pub fn delays(&self, n: usize) -> Vec<u16> {
+        // `hold` があれば最後のコマはそれだけ止める
+        let m = n - (n > 0 && self.hold.is_some()) as usize;
+        let clamp = |delay: f64| delay.round().clamp(MIN_DELAY as f64, u16::MAX as f64) as u16;
+        let mut delays = (0..m)
+            .map(|i| match &self.speed {
+                GifSpeed::Fixed => self.delay,
+                GifSpeed::RealTime(times) => match (times.get(i), times.get(i + 1)) {
+                    (Some(now), Some(next)) => clamp((next - now) / 10.0),
+                    _ => self.delay,
+                },
+                GifSpeed::Total(total) => {
+                    // 切り捨ての誤差がたまらないように、累計から引く
+                    let end = |i: usize| *total as u64 * i as u64 / m as u64;
+                    clamp((end(i + 1) - end(i)) as f64)
+                }
+            })
+            .collect_vec();
+        if m < n {
+            delays.extend(self.hold);
+        }
+        delays
+    }
+}
+
+/// コマを 1 つずつ足していく GIF。盤面は持たないので、長い手順でもメモリを食わない
+#[derive(Clone)]
+pub struct ReplayEncoder {
+    h: usize,
+    w: usize,
+    hints: (Vec<Vec<i32>>, Vec<Vec<i32>>),
+    givens: Vec<Vec<bool>>,
+    layout: Layout,
+    options: RenderOptions,
+    gif: Gif,
+}
+
+impl ReplayEncoder {
+    pub fn new(
+        h: usize,
+        w: usize,
+        hints: (Vec<Vec<i32>>, Vec<Vec<i32>>),
+        givens: Vec<Vec<bool>>,
+        options: RenderOptions,
+        loop_count: Option<u16>,
+    ) -> Self {
+        let layout = Layout::new(h, w, &hints, &options);
+        let gif = Gif::new(
+            layout.width as u16,
+            layout.height as u16,
+            &gif_palette(&options),
+            loop_count,
+        );
+        Self {
+            h,
+            w,
+            hints,
+            givens,
+            layout,
+            options,
+            gif,
+        }
+    }
+
+    /// `delay` は 1/100 秒単位
+    pub fn push(&mut self, frame: &ReplayFrame, delay: u16) {
+        let (h, w, layout, options) = (self.h, self.w, &self.layout, &self.options);
+        let mut canvas = raster_board_inner(
+            h,
+            w,
+            &frame.board,
+            &self.hints,
+            &self.givens,
+            layout,
+            options,
+        );
+        if let Some(cursor) = frame.cursor {
+            raster_cursor(&mut canvas, h, w, cursor, layout, options);
+        }
+        self.gif.add_canvas(&canvas, delay).unwrap();
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.gif.buffer
+    }
+
+    /// 今までのコマのあとに `last` を足した GIF。自分は変えないので、このあともコマを足せる
+    pub fn snapshot(&self, last: &ReplayFrame, delay: u16) -> Vec<u8> {
+        let mut encoder = self.clone();
+        encoder.push(last, delay);
+        encoder.finish()
+    }
+}
+
+/// 盤面をコマ送りした GIF。マス、バツ、カーソル、手がかりも描く
+pub fn vis_gif_inner(
+    h: usize,
+    w: usize,
+    frames: &[ReplayFrame],
+    hints: &(Vec<Vec<i32>>, Vec<Vec<i32>>),
+    givens: &[Vec<bool>],
+    options: &RenderOptions,
+    gif_options: &GifOptions,
+) -> Vec<u8> {
+    let mut encoder = ReplayEncoder::new(
+        h,
+        w,
+        hints.clone(),
+        givens.to_vec(),
+        options.clone(),
+        gif_options.loop_count,
+    );
+    if let (Some(intro), Some(first)) = (gif_options.intro, frames.first()) {
+        // 最初から決まっているマスだけを残す
+        let board = (0..h)
+            .map(|y| {
+                (0..w)
+                    .map(|x| first.board[y][x].filter(|_| givens[y][x]))
+                    .collect()
+            })
+            .collect();
+        encoder.push(
+            &ReplayFrame {
+                board,
+                cursor: None,
+            },
+            intro,
+        );
+    }
+    for (frame, delay) in frames.iter().zip(gif_options.delays(frames.len())) {
+        encoder.push(frame, delay);
+    }
+    encoder.finish()
+}
+
+/// 差分での書き換え 1 つ
+#[derive(Clone, Debug, PartialEq)]
+pub enum Patch {
+    /// `id` の要素の `name` を `value` にする
+    Set {
+        id: String,
+        name: &'static str,
+        value: String,
+    },
+    /// `parent` の要素の最後に、`markup` の要素 (id は `id`) を足す
+    Add {
+        id: String,
+        parent: &'static str,
+        markup: String,
+    },
+    /// `id` の要素を取り除く
+    Remove { id: String },
+}
+
+impl Patch {
+    pub fn to_json(&self) -> String {
+        match self {
+            Patch::Set { id, name, value } => format!(
+                r#"{{"op":"set","id":{},"name":{},"value":{}}}"#,
+                json_string(id),
+                json_string(name),
+                json_string(value)
+            ),
+            Patch::Add { id, parent, markup } => format!(
+                r#"{{"op":"add","id":{},"parent":{},"markup":{}}}"#,
+                json_string(id),
+                json_string(parent),
+                json_string(markup)
+            ),
+            Patch::Remove { id } => format!(r#"{{"op":"remove","id":{}}}"#, json_string(id)),
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut ret = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            c if (c as u32) < 0x20 => ret.push_str(&format!("\\u{:04x}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
 }
 
-pub fn vis_gif_inner(h: usize, w: usize, d: u16, boards: &[Vec<Vec<bool>>]) -> Vec<u8> {
-    let mut gif = Gif::new(h as u16 * d, w as u16 * d);
-    for (i, board) in boards.iter().enumerate() {
-        let delay = if i == boards.len() - 1 { 200 } else { 20 };
-        gif.add(board, delay).unwrap();
+/// 差分で書き換える要素の id
+fn cell_id(y: usize, x: usize) -> String {
+    format!("cell-{}-{}", y, x)
+}
+fn cross_id(y: usize, x: usize) -> String {
+    format!("cross-{}-{}", y, x)
+}
+/// バツの要素を入れる `<g>` の id
+const CROSSES_ID: &str = "crosses";
+fn row_clue_id(y: usize, i: usize) -> String {
+    format!("row-{}-{}", y, i)
+}
+fn column_clue_id(x: usize, i: usize) -> String {
+    format!("column-{}-{}", x, i)
+}
+
+pub fn cell_fill(palette: &Palette, given: bool, val: Option<bool>) -> &str {
+    match (given, val == Some(true)) {
+        (true, true) => &palette.given_fill,
+        (true, false) => &palette.given_background,
+        (false, true) => &palette.fill,
+        (false, false) => &palette.background,
     }
-    gif.buffer
 }
 
+fn cross_visibility(val: Option<bool>) -> &'static str {
+    if val == Some(false) {
+        "visible"
+    } else {
+        "hidden"
+    }
+}
+
+fn cross_path(y: usize, x: usize, layout: &Layout, options: &RenderOptions) -> Path {
+    let d = layout.cell_size;
+    Path::new()
+        .set("id", cross_id(y, x))
+        .set("fill", "none")
+        .set("stroke", options.palette.cross.as_str())
+        .set("stroke-width", options.cross_width)
+        .set(
+            "d",
+            Data::new()
+                .move_to((
+                    x as i32 * d + layout.origin_x,
+                    y as i32 * d + layout.origin_y,
+                ))
+                .line_by((d, d))
+                .move_by((0, -d))
+                .line_by((-d, d)),
+        )
+}
+
+type ClueFills<'a> = (Vec<Vec<&'a str>>, Vec<Vec<&'a str>>);
+
+/// 手がかりの数字の色
+pub fn clue_fills<'a>(
+    h: usize,
+    w: usize,
+    board: &[Vec<Option<bool>>],
+    hints: &(Vec<Vec<i32>>, Vec<Vec<i32>>),
+    options: &'a RenderOptions,
+) -> ClueFills<'a> {
+    let palette = &options.palette;
+    let hints_hidden = get_hints_hidden(board, hints);
+    let conflicts = if options.highlight_conflicts {
+        get_conflicts(board, hints)
+    } else {
+        (vec![false; h], vec![false; w])
+    };
+    let fill = |conflict: bool, hidden: bool| {
+        if conflict {
+            palette.conflict.as_str()
+        } else if hidden {
+            palette.clue_satisfied.as_str()
+        } else {
+            palette.clue.as_str()
+        }
+    };
+    let rows = hints_hidden
+        .0
+        .iter()
+        .zip(&conflicts.0)
+        .map(|(hidden, &conflict)| hidden.iter().map(|&f| fill(conflict, f)).collect())
+        .collect();
+    let columns = hints_hidden
+        .1
+        .iter()
+        .zip(&conflicts.1)
+        .map(|(hidden, &conflict)| hidden.iter().map(|&f| fill(conflict, f)).collect())
+        .collect();
+    (rows, columns)
+}
+
+/// 手がかりの配置は `layout` に従う。`Layout::new(h, w, hints, options)` で作る。
+/// マスとバツと手がかりには `vis_board_diff_inner` で書き換えるための id を付ける。
+/// バツの要素はバツのあるマスにだけ置く
 pub fn vis_board_inner(
     h: usize,
     w: usize,
-    board: &Vec<Vec<Option<bool>>>,
+    board: &[Vec<Option<bool>>],
     hints: &(Vec<Vec<i32>>, Vec<Vec<i32>>),
-    fill: &str,
-    offset_y: i32,
-    offset_x: i32,
+    givens: &[Vec<bool>],
+    layout: &Layout,
+    options: &RenderOptions,
 ) -> String {
-    let hints_hidden = get_hints_hidden(board, hints);
+    board_document(h, w, board, hints, givens, layout, options).to_string()
+}
+
+pub fn board_document(
+    h: usize,
+    w: usize,
+    board: &[Vec<Option<bool>>],
+    hints: &(Vec<Vec<i32>>, Vec<Vec<i32>>),
+    givens: &[Vec<bool>],
+    layout: &Layout,
+    options: &RenderOptions,
+) -> Document {
+    build_document(h, w, board, hints, givens, layout, options, false)
+}
+
+/// `every_cross` なら、バツのないマスにも見えないバツを置く。
+/// 要素を足したり消したりできない SMIL のアニメーションで使う
+#[allow(clippy::too_many_arguments)]
+fn build_document(
+    h: usize,
+    w: usize,
+    board: &[Vec<Option<bool>>],
+    hints: &(Vec<Vec<i32>>, Vec<Vec<i32>>),
+    givens: &[Vec<bool>],
+    layout: &Layout,
+    options: &RenderOptions,
+    every_cross: bool,
+) -> Document {
+    let d = layout.cell_size;
+    let (offset_y, offset_x) = (layout.origin_y, layout.origin_x);
+    let palette = &options.palette;
+    let clue_fills = clue_fills(h, w, board, hints, options);
 
     let mut doc = Document::new()
-        .set("id", "vis")
-        .set(
-            "viewBox",
-            (
-                -5,
-                -5,
-                D * w as i32 + offset_x + 10,
-                D * h as i32 + offset_y + 10,
-            ),
-        )
-        .set("width", D * w as i32 + offset_x + 10)
-        .set("height", D * h as i32 + offset_y + 10);
+        .set("viewBox", (0, 0, layout.width, layout.height))
+        .set("width", layout.width)
+        .set("height", layout.height);
+    if !options.compact {
+        doc = doc.set("id", "vis");
+    }
 
-    // grids
-    for y in 0..h {
-        for x in 0..w {
+    if options.compact {
+        let color = |y: usize, x: usize| cell_fill(palette, givens[y][x], board[y][x]);
+        for (color, data) in cell_runs(h, w, d, (offset_x, offset_y), color) {
+            doc = doc.add(Path::new().set("fill", color).set("d", data));
+        }
+        // 罫線は端から端までの線にする
+        let mut data = Data::new();
+        for y in 0..=h {
+            data = data
+                .move_to((offset_x, y as i32 * d + offset_y))
+                .horizontal_line_by(w as i32 * d);
+        }
+        for x in 0..=w {
+            data = data
+                .move_to((x as i32 * d + offset_x, offset_y))
+                .vertical_line_by(h as i32 * d);
+        }
+        doc = doc.add(
+            Path::new()
+                .set("fill", "none")
+                .set("stroke", palette.grid.as_str())
+                .set("stroke-width", options.grid_width)
+                .set("d", data),
+        );
+        let mut data = Data::new();
+        for (y, row) in board.iter().enumerate() {
+            for (x, &val) in row.iter().enumerate() {
+                if val == Some(false) {
+                    data = data
+                        .move_to((x as i32 * d + offset_x, y as i32 * d + offset_y))
+                        .line_by((d, d))
+                        .move_by((0, -d))
+                        .line_by((-d, d));
+                }
+            }
+        }
+        if !data.is_empty() {
             doc = doc.add(
-                Rectangle::new()
-                    .set(
-                        "fill",
-                        if board[y][x] == Some(true) {
-                            fill
-                        } else {
-                            "white"
-                        },
-                    )
-                    .set("x", x as i32 * D + offset_x)
-                    .set("y", y as i32 * D + offset_y)
-                    .set("width", D)
-                    .set("height", D)
-                    .set("stroke", "black")
-                    .set("stroke-width", 1),
+                Path::new()
+                    .set("fill", "none")
+                    .set("stroke", palette.cross.as_str())
+                    .set("stroke-width", options.cross_width)
+                    .set("d", data),
             );
         }
-    }
-
-    // crosses
-    for y in 0..h {
-        for x in 0..w {
-            if board[y][x] == Some(false) {
+    } else {
+        // grids
+        for y in 0..h {
+            for x in 0..w {
                 doc = doc.add(
-                    Path::new()
-                        .set("fill", "none")
-                        .set("stroke", "black")
-                        .set("stroke-width", 0.5)
-                        .set(
-                            "d",
-                            Data::new()
-                                .move_to((x as i32 * D + offset_x, y as i32 * D + offset_y))
-                                .line_by((D, D))
-                                .move_by((0, -D))
-                                .line_by((-D, D)),
-                        ),
+                    Rectangle::new()
+                        .set("id", cell_id(y, x))
+                        .set("fill", cell_fill(palette, givens[y][x], board[y][x]))
+                        .set("x", x as i32 * d + offset_x)
+                        .set("y", y as i32 * d + offset_y)
+                        .set("width", d)
+                        .set("height", d)
+                        .set("stroke", palette.grid.as_str())
+                        .set("stroke-width", options.grid_width),
                 );
             }
         }
+
+        // crosses。後から足せるように、罫線の上、枠の下に `<g>` を置く
+        let mut crosses = Group::new().set("id", CROSSES_ID);
+        for (y, row) in board.iter().enumerate() {
+            for (x, &val) in row.iter().enumerate() {
+                if every_cross {
+                    crosses = crosses.add(
+                        cross_path(y, x, layout, options).set("visibility", cross_visibility(val)),
+                    );
+                } else if val == Some(false) {
+                    crosses = crosses.add(cross_path(y, x, layout, options));
+                }
+            }
+        }
+        doc = doc.add(crosses);
     }
 
     // frames
-    for y in (0..h).step_by(5) {
-        for x in (0..w).step_by(5) {
+    let interval = options.major_interval;
+    for y in (0..h).step_by(interval) {
+        for x in (0..w).step_by(interval) {
             doc = doc.add(
                 Rectangle::new()
                     .set("fill-opacity", 0)
-                    .set("x", x as i32 * D + offset_x)
-                    .set("y", y as i32 * D + offset_y)
-                    .set("width", D * 5)
-                    .set("height", D * 5)
-                    .set("stroke", "black")
-                    .set("stroke-width", 4),
+                    .set("x", x as i32 * d + offset_x)
+                    .set("y", y as i32 * d + offset_y)
+                    .set("width", d * interval as i32)
+                    .set("height", d * interval as i32)
+                    .set("stroke", palette.frame.as_str())
+                    .set("stroke-width", options.frame_width),
             );
         }
     }
@@ -135,24 +791,18 @@ pub fn vis_board_inner(
     // hints
     for (y, hints) in hints.0.iter().enumerate() {
         for (x, num) in hints.iter().enumerate() {
-            let dx = if *num >= 10 { D / 4 } else { 0 };
+            let clue = &layout.rows[y][x];
+            let mut text = options.clue_text();
+            if !options.compact {
+                text = text.set("id", row_clue_id(y, x));
+            }
             doc = doc.add(
-                Text::new()
-                    .set(
-                        "x",
-                        offset_x - D * (hints.len() - 1 - x) as i32 - D / 2 + dx,
-                    )
-                    .set("y", offset_y + D * y as i32 + D / 2)
-                    .set(
-                        "fill",
-                        if hints_hidden.0[y][x] {
-                            "lightgray"
-                        } else {
-                            "black"
-                        },
-                    )
-                    .set("text-anchor", "end")
-                    .set("dominant-baseline", "middle")
+                text.set("x", clue.x)
+                    .set("y", clue.y)
+                    .set("font-size", clue.font_size)
+                    .set("fill", clue_fills.0[y][x])
+                    .set("text-anchor", "middle")
+                    .set("dominant-baseline", "central")
                     .add(svg::node::Text::new(format!("{}", num))),
             );
         }
@@ -160,42 +810,235 @@ pub fn vis_board_inner(
 
     for (x, hints) in hints.1.iter().enumerate() {
         for (y, num) in hints.iter().enumerate() {
+            let clue = &layout.columns[x][y];
+            let mut text = options.clue_text();
+            if !options.compact {
+                text = text.set("id", column_clue_id(x, y));
+            }
             doc = doc.add(
-                Text::new()
-                    .set("x", offset_x + D * x as i32 + D / 2)
-                    .set("y", offset_y - D * (hints.len() - 1 - y) as i32 - D / 2)
-                    .set(
-                        "fill",
-                        if hints_hidden.1[x][y] {
-                            "lightgray"
-                        } else {
-                            "black"
-                        },
-                    )
+                text.set("x", clue.x)
+                    .set("y", clue.y)
+                    .set("font-size", clue.font_size)
+                    .set("fill", clue_fills.1[x][y])
                     .set("text-anchor", "middle")
+                    .set("dominant-baseline", "central")
                     .add(svg::node::Text::new(format!("{}", num))),
             );
         }
     }
 
-    doc.to_string()
+    doc
+}
+
+/// `before` を描いた `vis_board_inner` の SVG を `after` にするための書き換え。
+/// 変わったマスと、色の変わった手がかりだけを返す。バツは要素ごと足したり消したりする
+pub fn vis_board_diff_inner(
+    h: usize,
+    w: usize,
+    before: &[Vec<Option<bool>>],
+    after: &[Vec<Option<bool>>],
+    hints: &(Vec<Vec<i32>>, Vec<Vec<i32>>),
+    givens: &[Vec<bool>],
+    options: &RenderOptions,
+) -> Vec<Patch> {
+    let palette = &options.palette;
+    let layout = Layout::new(h, w, hints, options);
+    let mut patches = vec![];
+    for y in 0..h {
+        for x in 0..w {
+            let (old, new) = (before[y][x], after[y][x]);
+            if cell_fill(palette, givens[y][x], old) != cell_fill(palette, givens[y][x], new) {
+                patches.push(Patch::Set {
+                    id: cell_id(y, x),
+                    name: "fill",
+                    value: cell_fill(palette, givens[y][x], new).to_string(),
+                });
+            }
+            if old != Some(false) && new == Some(false) {
+                patches.push(Patch::Add {
+                    id: cross_id(y, x),
+                    parent: CROSSES_ID,
+                    markup: cross_path(y, x, &layout, options).to_string(),
+                });
+            } else if old == Some(false) && new != Some(false) {
+                patches.push(Patch::Remove { id: cross_id(y, x) });
+            }
+        }
+    }
+
+    let old = clue_fills(h, w, before, hints, options);
+    let new = clue_fills(h, w, after, hints, options);
+    for y in 0..h {
+        for i in 0..hints.0[y].len() {
+            if old.0[y][i] != new.0[y][i] {
+                patches.push(Patch::Set {
+                    id: row_clue_id(y, i),
+                    name: "fill",
+                    value: new.0[y][i].to_string(),
+                });
+            }
+        }
+    }
+    for x in 0..w {
+        for i in 0..hints.1[x].len() {
+            if old.1[x][i] != new.1[x][i] {
+                patches.push(Patch::Set {
+                    id: column_clue_id(x, i),
+                    name: "fill",
+                    value: new.1[x][i].to_string(),
+                });
+            }
+        }
+    }
+    patches
+}
+
+/// コマを 1 つずつ足していく SMIL のアニメーション付きの SVG。`ReplayEncoder` と同じく、
+/// 盤面は直前の 1 つしか持たず、変わったマスの `<set>` だけを足していく
+#[derive(Clone)]
+pub struct AnimatedEncoder {
+    h: usize,
+    w: usize,
+    hints: (Vec<Vec<i32>>, Vec<Vec<i32>>),
+    givens: Vec<Vec<bool>>,
+    layout: Layout,
+    options: RenderOptions,
+    doc: Option<Document>,
+    previous: Vec<Vec<Option<bool>>>,
+    /// 次のコマを出す時刻 (1/100 秒単位)
+    time: u32,
+}
+
+impl AnimatedEncoder {
+    pub fn new(
+        h: usize,
+        w: usize,
+        hints: (Vec<Vec<i32>>, Vec<Vec<i32>>),
+        givens: Vec<Vec<bool>>,
+        options: &RenderOptions,
+    ) -> Self {
+        // マスごとの要素に id がいるので、まとめた描き方はしない
+        let options = RenderOptions {
+            compact: false,
+            ..options.clone()
+        };
+        let layout = Layout::new(h, w, &hints, &options);
+        Self {
+            h,
+            w,
+            hints,
+            givens,
+            layout,
+            options,
+            doc: None,
+            previous: vec![],
+            time: 0,
+        }
+    }
+
+    /// `board` を `delay` (1/100 秒単位) のあいだ見せる
+    pub fn push(&mut self, board: &[Vec<Option<bool>>], delay: u16) {
+        let (h, w, hints, givens) = (self.h, self.w, &self.hints, &self.givens);
+        let doc = match self.doc.take() {
+            None => build_document(
+                h,
+                w,
+                board,
+                hints,
+                givens,
+                &self.layout,
+                &self.options,
+                true,
+            ),
+            Some(mut doc) => {
+                let (time, previous) = (self.time, &self.previous);
+                for patch in
+                    vis_board_diff_inner(h, w, previous, board, hints, givens, &self.options)
+                {
+                    // バツはすべて置いてあるので、足す・消すは見える・見えないにする
+                    let (id, name, value) = match patch {
+                        Patch::Set { id, name, value } => (id, name, value),
+                        Patch::Add { id, .. } => (id, "visibility", "visible".to_string()),
+                        Patch::Remove { id } => (id, "visibility", "hidden".to_string()),
+                    };
+                    let mut set = Element::new("set");
+                    set.assign("href", format!("#{}", id));
+                    set.assign("attributeName", name);
+                    set.assign("to", value);
+                    set.assign("begin", format!("{}.{:02}s", time / 100, time % 100));
+                    set.assign("fill", "freeze");
+                    doc = doc.add(set);
+                }
+                doc
+            }
+        };
+        self.doc = Some(doc);
+        self.previous = board.to_vec();
+        self.time += delay as u32;
+    }
+
+    /// コマがなければ空文字列
+    pub fn finish(self) -> String {
+        self.doc.map_or_else(String::new, |doc| doc.to_string())
+    }
+
+    /// 今までのコマのあとに `last` を足した SVG。自分は変えないので、このあともコマを足せる
+    pub fn snapshot(&self, last: &[Vec<Option<bool>>]) -> String {
+        let mut encoder = self.clone();
+        encoder.push(last, 0);
+        encoder.finish()
+    }
+}
+
+/// 盤面の移り変わりを SMIL のアニメーションにした SVG。`vis_board_inner` の SVG に、
+/// `vis_board_diff_inner` の書き換えを `<set>` で時刻どおりに足す。
+/// 時間は `gif_options` に従い、一度だけ流れて最後の盤面で止まる
+pub fn vis_animated_inner(
+    h: usize,
+    w: usize,
+    frames: &[ReplayFrame],
+    hints: &(Vec<Vec<i32>>, Vec<Vec<i32>>),
+    givens: &[Vec<bool>],
+    options: &RenderOptions,
+    gif_options: &GifOptions,
+) -> String {
+    let mut encoder = AnimatedEncoder::new(h, w, hints.clone(), givens.to_vec(), options);
+    if let (Some(delay), Some(first)) = (gif_options.intro, frames.first()) {
+        // 最初から決まっているマスだけを残す
+        let intro = (0..h)
+            .map(|y| {
+                (0..w)
+                    .map(|x| first.board[y][x].filter(|_| givens[y][x]))
+                    .collect_vec()
+            })
+            .collect_vec();
+        encoder.push(&intro, delay);
+    }
+    for (frame, delay) in frames.iter().zip(gif_options.delays(frames.len())) {
+        encoder.push(&frame.board, delay);
+    }
+    encoder.finish()
 }
 
 pub fn vis_gaming_boards_inner(
     h: usize,
     w: usize,
-    board: &Vec<Vec<Option<bool>>>,
+    board: &[Vec<Option<bool>>],
     hints: &(Vec<Vec<i32>>, Vec<Vec<i32>>),
-    offset_y: i32,
-    offset_x: i32,
+    givens: &[Vec<bool>],
+    layout: &Layout,
+    options: &RenderOptions,
 ) -> String {
-    [
-        "#ff0000", "#ff7f00", "#ffff00", "#7fff00", "#00ff00", "#00ff7f", "#00ffff", "#007fff",
-        "#0000ff", "#7f00ff", "#ff00ff", "#ff007f",
-    ]
-    .iter()
-    .map(|&fill| vis_board_inner(h, w, board, hints, fill, offset_y, offset_x))
-    .join("$")
+    options
+        .palette
+        .gaming
+        .iter()
+        .map(|fill| {
+            let mut options = options.clone();
+            options.palette.fill = fill.clone();
+            vis_board_inner(h, w, board, hints, givens, layout, &options)
+        })
+        .join("$")
 }
 
 pub fn vis_cursor_inner(
@@ -203,56 +1046,51 @@ pub fn vis_cursor_inner(
     w: usize,
     y: usize,
     x: usize,
-    offset_y: i32,
-    offset_x: i32,
+    layout: &Layout,
+    options: &RenderOptions,
 ) -> String {
+    let d = layout.cell_size;
+    let (offset_y, offset_x) = (layout.origin_y, layout.origin_x);
+    let cursor = options.palette.cursor.as_str();
     let mut doc = Document::new()
         .set("id", "vis")
-        .set(
-            "viewBox",
-            (
-                -5,
-                -5,
-                D * w as i32 + offset_x + 10,
-                D * h as i32 + offset_y + 10,
-            ),
-        )
-        .set("width", D * w as i32 + offset_x + 10)
-        .set("height", D * h as i32 + offset_y + 10);
+        .set("viewBox", (0, 0, layout.width, layout.height))
+        .set("width", layout.width)
+        .set("height", layout.height);
 
     // cursor
     doc = doc.add(
         Rectangle::new()
-            .set("x", x as i32 * D + offset_x)
-            .set("y", y as i32 * D + offset_y)
-            .set("width", D)
-            .set("height", D)
+            .set("x", x as i32 * d + offset_x)
+            .set("y", y as i32 * d + offset_y)
+            .set("width", d)
+            .set("height", d)
             .set("fill-opacity", 0)
-            .set("stroke", THEME)
-            .set("stroke-width", 4),
+            .set("stroke", cursor)
+            .set("stroke-width", options.cursor_width),
     );
 
     // highlights
     doc = doc.add(
         Rectangle::new()
             .set("x", 0)
-            .set("y", D * y as i32 + offset_y)
-            .set("width", D * w as i32 + offset_x)
-            .set("height", D)
-            .set("fill", THEME)
+            .set("y", d * y as i32 + offset_y)
+            .set("width", d * w as i32 + offset_x)
+            .set("height", d)
+            .set("fill", cursor)
             .set("fill-opacity", 0.2)
-            .set("stroke", THEME)
+            .set("stroke", cursor)
             .set("stroke-width", 0),
     );
     doc = doc.add(
         Rectangle::new()
-            .set("x", D * x as i32 + offset_x)
+            .set("x", d * x as i32 + offset_x)
             .set("y", 0)
-            .set("width", D)
-            .set("height", D * h as i32 + offset_x)
-            .set("fill", THEME)
+            .set("width", d)
+            .set("height", d * h as i32 + offset_y)
+            .set("fill", cursor)
             .set("fill-opacity", 0.2)
-            .set("stroke", THEME)
+            .set("stroke", cursor)
             .set("stroke-width", 0),
     );
 
@@ -265,9 +1103,14 @@ pub fn set_inner(
     val: Option<bool>,
     board: &mut Vec<Vec<Option<bool>>>,
     hints: &(Vec<Vec<i32>>, Vec<Vec<i32>>),
+    givens: &[Vec<bool>],
 ) {
     let (h, w) = (board.len(), board[0].len());
 
+    // 最初から決まっているマスは変えられない
+    if givens[y][x] {
+        return;
+    }
     board[y][x] = val;
 
     // y軸方向
@@ -281,7 +1124,7 @@ pub fn set_inner(
         if filled == hints.0[y] {
             for x in 0..w {
                 if board[y][x].is_none() {
-                    set_inner(y, x, Some(true), board, hints);
+                    set_inner(y, x, Some(true), board, hints, givens);
                 }
             }
         }
@@ -291,7 +1134,7 @@ pub fn set_inner(
         if filled == hints.0[y] {
             for x in 0..w {
                 if board[y][x].is_none() {
-                    set_inner(y, x, Some(false), board, hints);
+                    set_inner(y, x, Some(false), board, hints, givens);
                 }
             }
         }
@@ -316,7 +1159,7 @@ pub fn set_inner(
                     Some(false) => {}
                     None => {
                         if x > 0 && board[y][x - 1] == Some(true) {
-                            set_inner(y, x, Some(false), board, hints);
+                            set_inner(y, x, Some(false), board, hints, givens);
                         }
                         break;
                     }
@@ -345,7 +1188,7 @@ pub fn set_inner(
                     Some(false) => {}
                     None => {
                         if x + 1 < w && board[y][x + 1] == Some(true) {
-                            set_inner(y, x, Some(false), board, hints);
+                            set_inner(y, x, Some(false), board, hints, givens);
                         }
                         break;
                     }
@@ -366,7 +1209,7 @@ pub fn set_inner(
         if filled == hints.1[x] {
             for y in 0..h {
                 if board[y][x].is_none() {
-                    set_inner(y, x, Some(true), board, hints);
+                    set_inner(y, x, Some(true), board, hints, givens);
                 }
             }
         }
@@ -376,7 +1219,7 @@ pub fn set_inner(
         if filled == hints.1[x] {
             for y in 0..h {
                 if board[y][x].is_none() {
-                    set_inner(y, x, Some(false), board, hints);
+                    set_inner(y, x, Some(false), board, hints, givens);
                 }
             }
         }
@@ -401,7 +1244,7 @@ pub fn set_inner(
                     Some(false) => {}
                     None => {
                         if y > 0 && board[y - 1][x] == Some(true) {
-                            set_inner(y, x, Some(false), board, hints);
+                            set_inner(y, x, Some(false), board, hints, givens);
                         }
                         break;
                     }
@@ -430,7 +1273,7 @@ pub fn set_inner(
                     Some(false) => {}
                     None => {
                         if y + 1 < h && board[y + 1][x] == Some(true) {
-                            set_inner(y, x, Some(false), board, hints);
+                            set_inner(y, x, Some(false), board, hints, givens);
                         }
                         break;
                     }
@@ -457,330 +1300,363 @@ fn compress(vec: &[bool]) -> Vec<i32> {
     ret
 }
 
+/// 手がかりを満たすように塗れなくなった行と列
+fn get_conflicts(
+    board: &[Vec<Option<bool>>],
+    hints: &(Vec<Vec<i32>>, Vec<Vec<i32>>),
+) -> (Vec<bool>, Vec<bool>) {
+    let (h, w) = (board.len(), board[0].len());
+    let clues = |hints: &[i32]| hints.iter().map(|&num| num as usize).collect_vec();
+    let rows = (0..h)
+        .map(|y| !line::is_consistent(&board[y], &clues(&hints.0[y])))
+        .collect();
+    let columns = (0..w)
+        .map(|x| {
+            let line = (0..h).map(|y| board[y][x]).collect_vec();
+            !line::is_consistent(&line, &clues(&hints.1[x]))
+        })
+        .collect();
+    (rows, columns)
+}
+
+/// 位置が確定して塗られている手がかり。`line::satisfied` で 1 列ずつ調べる
 fn get_hints_hidden(
-    board: &Vec<Vec<Option<bool>>>,
+    board: &[Vec<Option<bool>>],
     hints: &(Vec<Vec<i32>>, Vec<Vec<i32>>),
 ) -> (Vec<Vec<bool>>, Vec<Vec<bool>>) {
     let (h, w) = (board.len(), board[0].len());
-    let mut hints_hidden = (
-        hints
-            .0
-            .iter()
-            .map(|hints| hints.iter().map(|_| false).collect::<Vec<_>>())
-            .collect::<Vec<_>>(),
-        hints
-            .1
-            .iter()
-            .map(|hints| hints.iter().map(|_| false).collect::<Vec<_>>())
-            .collect::<Vec<_>>(),
-    );
-    for y in 0..h {
-        // y軸方向
-        let true_count = (0..w).filter(|&x| board[y][x] == Some(true)).count();
-        let none_count = (0..w).filter(|&x| board[y][x].is_none()).count();
-        if true_count > hints.0[y].iter().sum::<i32>() as usize {
-            // 全部間違っていることにする
-            for hidden in &mut hints_hidden.0[y] {
-                *hidden = false;
-            }
-        } else if none_count == 0 {
-            let line = (0..w).map(|x| board[y][x].unwrap()).collect_vec();
-            let filled = compress(&line);
-            if filled == hints.0[y] {
-                // 全部正しいことにする
-                for hidden in &mut hints_hidden.0[y] {
-                    *hidden = true;
-                }
-            } else {
-                // 全部間違っていることにする
-                for hidden in &mut hints_hidden.0[y] {
-                    *hidden = false;
-                }
-            }
-        } else {
-            // (1) None <- true として完全一致か？
-            let line = (0..w).map(|x| board[y][x] != Some(false)).collect_vec();
-            let filled = compress(&line);
-            if filled == hints.0[y] {
-                for hidden in &mut hints_hidden.0[y] {
-                    *hidden = true;
-                }
-            }
-            // (2) None <- false として完全一致か？
-            let line = (0..w).map(|x| board[y][x] == Some(true)).collect_vec();
-            let filled = compress(&line);
-            if filled == hints.0[y] {
-                for hidden in &mut hints_hidden.0[y] {
-                    *hidden = true;
-                }
-            }
-            // (3) 前から見てどこまで一致するか？
-            if (0..w).any(|x| board[y][x].is_none()) {
-                let mut x = 0;
-                let mut i = 0;
-                while x < w && i < hints.0[y].len() {
-                    let mut nx = x;
-                    while nx < w && board[y][x] == board[y][nx] {
-                        nx += 1;
-                    }
-                    let len = (nx - x) as i32;
-                    match board[y][x] {
-                        Some(true) => {
-                            if len != hints.0[y][i] {
-                                break;
-                            } else {
-                                hints_hidden.0[y][i] = true;
-                                i += 1;
-                            }
-                        }
-                        Some(false) => {}
-                        None => {
-                            break;
-                        }
-                    }
-                    x = nx;
-                }
-            }
-            // (4) 後から見てどこまで一致するか？
-            if (0..w).any(|x| board[y][x].is_none()) {
-                let mut x = w - 1;
-                let mut i = hints.0[y].len() - 1;
-                while x < w && i < hints.0[y].len() {
-                    let mut nx = x;
-                    while nx < w && board[y][x] == board[y][nx] {
-                        nx -= 1;
-                    }
-                    let len = (x - nx) as i32;
-                    match board[y][x] {
-                        Some(true) => {
-                            if len != hints.0[y][i] {
-                                break;
-                            } else {
-                                hints_hidden.0[y][i] = true;
-                                i -= 1;
-                            }
-                        }
-                        Some(false) => {}
-                        None => {
-                            break;
-                        }
-                    }
-                    x = nx;
-                }
-            }
+    let clues = |hints: &[i32]| hints.iter().map(|&num| num as usize).collect_vec();
+    let rows = (0..h)
+        .map(|y| line::satisfied(&board[y], &clues(&hints.0[y])))
+        .collect();
+    let columns = (0..w)
+        .map(|x| {
+            let line = (0..h).map(|y| board[y][x]).collect_vec();
+            line::satisfied(&line, &clues(&hints.1[x]))
+        })
+        .collect();
+    (rows, columns)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {}
+
+    #[test]
+    fn test_cursor() {
+        let layout = Layout {
+            origin_x: 10,
+            origin_y: 50,
+            cell_size: 24,
+            width: 92,
+            height: 108,
+            rows: vec![],
+            columns: vec![],
+        };
+        let svg = vis_cursor_inner(2, 3, 1, 2, &layout, &Default::default());
+        // 列の強調は手がかりの上端から盤面の下端まで
+        assert!(svg.contains(r#"height="98""#));
+        assert!(!svg.contains(r#"height="58""#));
+    }
+
+    #[test]
+    fn test_render_options() {
+        let board = vec![vec![Some(true), Some(false)], vec![Some(false), Some(true)]];
+        let hints = (vec![vec![1], vec![1]], vec![vec![1], vec![1]]);
+        let givens = vec![vec![false; 2]; 2];
+        let options = RenderOptions::default();
+        let layout = Layout::new(2, 2, &hints, &options);
+        let svg = vis_board_inner(2, 2, &board, &hints, &givens, &layout, &options);
+        assert!(svg.contains(r#"fill="black""#) && svg.contains(r#"width="24""#));
+
+        let mut options = RenderOptions {
+            cell_size: 30,
+            font_family: Some("monospace".to_string()),
+            ..Default::default()
+        };
+        options.palette.fill = "navy".to_string();
+        options.palette.clue_satisfied = "silver".to_string();
+        let layout = Layout::new(2, 2, &hints, &options);
+        let svg = vis_board_inner(2, 2, &board, &hints, &givens, &layout, &options);
+        assert!(svg.contains(r#"fill="navy""#) && svg.contains(r#"width="30""#));
+        assert!(svg.contains(r#"font-family="monospace""#));
+        // すべての手がかりが満たされている
+        assert!(svg.contains(r#"fill="silver""#));
+    }
+
+    #[test]
+    fn test_presets() {
+        assert_eq!(
+            RenderOptions::preset(Preset::Light),
+            RenderOptions::default()
+        );
+        let board = vec![vec![Some(true), Some(false)], vec![Some(false), Some(true)]];
+        let hints = (vec![vec![1], vec![1]], vec![vec![1], vec![1]]);
+        let givens = vec![vec![false; 2]; 2];
+        for preset in [Preset::Dark, Preset::HighContrast, Preset::ColorBlind] {
+            let options = RenderOptions::preset(preset);
+            assert_ne!(options.palette.cursor, Palette::default().cursor);
+            let layout = Layout::new(2, 2, &hints, &options);
+            let svgs = vis_gaming_boards_inner(2, 2, &board, &hints, &givens, &layout, &options);
+            assert_eq!(svgs.split('$').count(), options.palette.gaming.len());
         }
     }
-    for x in 0..w {
-        let true_count = (0..h).filter(|&y| board[y][x] == Some(true)).count();
-        let none_count = (0..h).filter(|&y| board[y][x].is_none()).count();
-        if true_count > hints.1[x].iter().sum::<i32>() as usize {
-            for hidden in &mut hints_hidden.1[x] {
-                *hidden = false;
-            }
-        } else if none_count == 0 {
-            let line = (0..h).map(|y| board[y][x].unwrap()).collect_vec();
-            let filled = compress(&line);
-            if filled == hints.1[x] {
-                // 全部正しいことにする
-                for hidden in &mut hints_hidden.1[x] {
-                    *hidden = true;
-                }
-            } else {
-                // 全部間違っていることにする
-                for hidden in &mut hints_hidden.1[x] {
-                    *hidden = false;
-                }
-            }
-        } else {
-            // (1) None <- true として完全一致か？
-            let line = (0..h).map(|y| board[y][x] != Some(false)).collect_vec();
-            let filled = compress(&line);
-            if filled == hints.1[x] {
-                for hidden in &mut hints_hidden.1[x] {
-                    *hidden = true;
-                }
-            }
-            // (2) None <- false として完全一致か？
-            let line = (0..h).map(|y| board[y][x] == Some(true)).collect_vec();
-            let filled = compress(&line);
-            if filled == hints.1[x] {
-                for hidden in &mut hints_hidden.1[x] {
-                    *hidden = true;
-                }
+
+    #[test]
+    fn test_conflicts() {
+        // 1 行目だけ塗りすぎている
+        let board = vec![vec![Some(true), Some(true)], vec![Some(false), None]];
+        let hints = (vec![vec![1], vec![1]], vec![vec![1], vec![1]]);
+        let givens = vec![vec![false; 2]; 2];
+        let mut options = RenderOptions::default();
+        let layout = Layout::new(2, 2, &hints, &options);
+        let svg = vis_board_inner(2, 2, &board, &hints, &givens, &layout, &options);
+        assert!(!svg.contains(r#"fill="red""#));
+        options.highlight_conflicts = true;
+        let svg = vis_board_inner(2, 2, &board, &hints, &givens, &layout, &options);
+        assert_eq!(svg.matches(r#"fill="red""#).count(), 1);
+    }
+
+    #[test]
+    fn test_diff() {
+        let hints = (vec![vec![1], vec![1]], vec![vec![1], vec![1]]);
+        let givens = vec![vec![false; 2]; 2];
+        let options = RenderOptions::default();
+        let before = vec![vec![Some(true), None], vec![None, None]];
+        let mut after = before.clone();
+        set_inner(1, 1, Some(true), &mut after, &hints, &givens);
+        let patches = vis_board_diff_inner(2, 2, &before, &after, &hints, &givens, &options);
+        // 塗ったマスと、自動でバツになったマスと、満たされた手がかり
+        assert!(patches.contains(&Patch::Set {
+            id: "cell-1-1".to_string(),
+            name: "fill",
+            value: "black".to_string(),
+        }));
+        let layout = Layout::new(2, 2, &hints, &options);
+        assert!(patches.contains(&Patch::Add {
+            id: "cross-0-1".to_string(),
+            parent: "crosses",
+            markup: cross_path(0, 1, &layout, &options).to_string(),
+        }));
+        let id = |patch: &Patch| match patch {
+            Patch::Set { id, .. } | Patch::Add { id, .. } | Patch::Remove { id } => id.clone(),
+        };
+        assert!(patches.iter().all(|patch| id(patch) != "cell-0-0"));
+        assert!(patches.iter().any(|patch| id(patch) == "row-1-0"));
+        // 書き換える要素と足す先はすべて SVG にあり、バツはバツのあるマスにしかない
+        let svg = vis_board_inner(2, 2, &before, &hints, &givens, &layout, &options);
+        assert!(svg.contains(r#"id="crosses""#));
+        assert!(!svg.contains("cross-"));
+        for patch in &patches {
+            if let Patch::Set { id, .. } = patch {
+                assert!(svg.contains(&format!(r#"id="{}""#, id)));
             }
-            // (3) 前から見てどこまで一致するか？
-            if (0..h).any(|y| board[y][x].is_none()) {
-                let mut y = 0;
-                let mut i = 0;
-                while y < h && i < hints.1[x].len() {
-                    let mut ny = y;
-                    while ny < h && board[y][x] == board[ny][x] {
-                        ny += 1;
-                    }
-                    let len = (ny - y) as i32;
-                    match board[y][x] {
-                        Some(true) => {
-                            if len != hints.1[x][i] {
-                                break;
-                            } else {
-                                hints_hidden.1[x][i] = true;
-                                i += 1;
-                            }
-                        }
-                        Some(false) => {}
-                        None => {
-                            break;
-                        }
-                    }
-                    y = ny;
-                }
+        }
+        let svg = vis_board_inner(2, 2, &after, &hints, &givens, &layout, &options);
+        assert_eq!(svg.matches("cross-").count(), 2);
+        assert!(vis_board_diff_inner(2, 2, &after, &after, &hints, &givens, &options).is_empty());
+        // バツを消す
+        let patches = vis_board_diff_inner(2, 2, &after, &before, &hints, &givens, &options);
+        assert!(patches.contains(&Patch::Remove {
+            id: "cross-0-1".to_string()
+        }));
+        assert_eq!(
+            Patch::Set {
+                id: "a".to_string(),
+                name: "fill",
+                value: "\"red\"".to_string(),
             }
-            // (4) 後から見てどこまで一致するか？
-            if (0..h).any(|y| board[y][x].is_none()) {
-                let mut y = h - 1;
-                let mut i = hints.1[x].len() - 1;
-                while y < h && i < hints.1[x].len() {
-                    let mut ny = y;
-                    while ny < h && board[y][x] == board[ny][x] {
-                        ny -= 1;
-                    }
-                    let len = (y - ny) as i32;
-                    match board[y][x] {
-                        Some(true) => {
-                            if len != hints.1[x][i] {
-                                break;
-                            } else {
-                                hints_hidden.1[x][i] = true;
-                                i -= 1;
-                            }
-                        }
-                        Some(false) => {}
-                        None => {
-                            break;
-                        }
-                    }
-                    y = ny;
-                }
+            .to_json(),
+            r#"{"op":"set","id":"a","name":"fill","value":"\"red\""}"#
+        );
+        assert_eq!(
+            Patch::Remove {
+                id: "a".to_string()
             }
-        }
+            .to_json(),
+            r#"{"op":"remove","id":"a"}"#
+        );
     }
 
-    hints_hidden
-}
-
-mod gif {
-    use weezl::{encode::Encoder, BitOrder, LzwError};
-    pub struct Gif {
-        width: u16,
-        height: u16,
-        pub buffer: Vec<u8>,
-    }
-    impl Gif {
-        pub fn new(width: u16, height: u16) -> Self {
-            let width_upper = (width >> 8) as u8;
-            let width_lower = (width % (1 << 8)) as u8;
-            let height_upper = (height >> 8) as u8;
-            let height_lower = (height % (1 << 8)) as u8;
-            let header = [
-                0x47,
-                0x49,
-                0x46,
-                0x38,
-                0x39,
-                0x61,
-                width_lower,
-                width_upper,
-                height_lower,
-                height_upper,
-                0x80,
-                0x00,
-                0x00,
-                0xff,
-                0xff,
-                0xff,
-                0x00,
-                0x00,
-                0x00,
-            ];
-
-            let application_extention = [
-                0x21, 0xff, 0x0b, 0x4e, 0x45, 0x54, 0x53, 0x43, 0x41, 0x50, 0x45, 0x32, 0x2e, 0x30,
-                0x03, 0x01, 0x00, 0x00, 0x00,
-            ];
-
-            let trailer = [0x3b];
-
-            let mut buffer = Vec::<u8>::new();
-            buffer.extend_from_slice(&header);
-            buffer.extend_from_slice(&application_extention);
-            buffer.extend_from_slice(&trailer);
-            Self {
-                width,
-                height,
-                buffer,
-            }
-        }
+    #[test]
+    fn test_compact() {
+        let n = 20;
+        let board = (0..n)
+            .map(|y| (0..n).map(|x| Some((x * y) % 3 == 0)).collect_vec())
+            .collect_vec();
+        let hints = (vec![vec![1]; n], vec![vec![1]; n]);
+        let givens = vec![vec![false; n]; n];
+        let mut options = RenderOptions::default();
+        let layout = Layout::new(n, n, &hints, &options);
+        let full = vis_board_inner(n, n, &board, &hints, &givens, &layout, &options);
+        options.compact = true;
+        let compact = vis_board_inner(n, n, &board, &hints, &givens, &layout, &options);
+        let elements = |svg: &str| svg.matches("<rect").count() + svg.matches("<path").count();
+        assert!(elements(&compact) * 10 < elements(&full));
+        assert!(compact.len() * 2 < full.len());
+        // 背景と塗りの 2 色、罫線、バツ
+        assert_eq!(compact.matches("<path").count(), 4);
+        assert!(!compact.contains("id="));
 
-        pub fn add(&mut self, data: &[Vec<bool>], delay: u16) -> Result<(), LzwError> {
-            let (h, w) = (data.len(), data[0].len());
-            let size_h = self.height as usize / h;
-            let size_w = self.height as usize / w;
-            let data = (0..self.height as usize)
-                .flat_map(|y| {
-                    (0..self.width as usize).map(move |x| data[y / size_h][x / size_w] as u8)
-                })
-                .collect::<Vec<_>>();
-            self.add_inner(&data, delay)?;
-            Ok(())
-        }
-
-        fn add_inner(&mut self, data: &[u8], delay: u16) -> Result<(), LzwError> {
-            let trailer = self.buffer.pop().unwrap();
-
-            let delay_upper = (delay >> 8) as u8;
-            let delay_lower = (delay % (1 << 8)) as u8;
-            let graphic_control_extention =
-                [0x21, 0xf9, 0x04, 0x04, delay_lower, delay_upper, 0x00, 0x00];
-            self.buffer.extend_from_slice(&graphic_control_extention);
-
-            let width_upper = (self.width >> 8) as u8;
-            let width_lower = (self.width % (1 << 8)) as u8;
-            let height_upper = (self.height >> 8) as u8;
-            let height_lower = (self.height % (1 << 8)) as u8;
-            let mut image_block = Vec::<u8>::new();
-            image_block.extend_from_slice(&[
-                0x2c,
-                0x00,
-                0x00,
-                0x00,
-                0x00,
-                width_lower,
-                width_upper,
-                height_lower,
-                height_upper,
-                0x00,
-                0x02,
-            ]);
-            let buffer = Encoder::new(BitOrder::Lsb, 2).encode(data)?;
-            let max_size = 0xff;
-            for l in (0..buffer.len()).step_by(max_size) {
-                let r = (l + max_size).min(buffer.len());
-                let block_size = (r - l) as u8;
-                image_block.push(block_size);
-                image_block.extend_from_slice(&buffer[l..r]);
-            }
-            image_block.push(0x00);
-            self.buffer.append(&mut image_block);
-            self.buffer.push(trailer);
-            Ok(())
-        }
+        let compact = vis_grid_inner(n, n, &board, &options);
+        assert_eq!(elements(&compact), 2);
+        assert!(!compact.contains("id="));
     }
-}
 
-fn gen_from_density(n: usize, density: usize) {}
+    #[test]
+    fn test_gif() {
+        let hints = (vec![vec![1], vec![1]], vec![vec![1], vec![1]]);
+        let givens = vec![vec![false; 2]; 2];
+        let options = RenderOptions::default();
+        let palette = gif_palette(&options);
+        assert!(palette.len() <= 256);
+        assert_eq!(palette[0], [255, 255, 255]);
+        let frames = [
+            ReplayFrame {
+                board: vec![vec![None; 2]; 2],
+                cursor: Some((0, 0)),
+            },
+            ReplayFrame {
+                board: vec![vec![Some(true), Some(false)], vec![None, None]],
+                cursor: None,
+            },
+        ];
+        let gif = vis_gif_inner(
+            2,
+            2,
+            &frames,
+            &hints,
+            &givens,
+            &options,
+            &GifOptions::default(),
+        );
+        let layout = Layout::new(2, 2, &hints, &options);
+        assert!(gif.starts_with(b"GIF89a"));
+        assert_eq!(gif[6..10], [layout.width as u8, 0, layout.height as u8, 0]);
+        // グラフィック制御拡張がコマの数だけある
+        assert_eq!(
+            gif.windows(3).filter(|b| b == &[0x21, 0xf9, 0x04]).count(),
+            2
+        );
+
+        // 1 コマずつ足しても同じ GIF になる
+        let mut encoder =
+            ReplayEncoder::new(2, 2, hints.clone(), givens.clone(), options.clone(), None);
+        encoder.push(&frames[0], 20);
+        // 途中で書き出しても記録は続けられる
+        assert_eq!(encoder.snapshot(&frames[1], 200), gif);
+        encoder.push(&frames[1], 200);
+        assert_eq!(encoder.finish(), gif);
+
+        // 最初に何も塗っていない問題のコマが増える
+        let gif_options = GifOptions {
+            intro: Some(100),
+            loop_count: Some(1),
+            ..Default::default()
+        };
+        let gif = vis_gif_inner(2, 2, &frames, &hints, &givens, &options, &gif_options);
+        assert_eq!(
+            gif.windows(3).filter(|b| b == &[0x21, 0xf9, 0x04]).count(),
+            3
+        );
+        assert!(!gif.windows(11).any(|b| b == b"NETSCAPE2.0"));
+    }
 
-#[cfg(test)]
-mod test {
     #[test]
-    fn test() {}
+    fn test_animated() {
+        let hints = (vec![vec![1], vec![1]], vec![vec![1], vec![1]]);
+        let givens = vec![vec![false, false], vec![false, true]];
+        let options = RenderOptions {
+            compact: true,
+            ..Default::default()
+        };
+        let frames = vec![
+            vec![vec![None, None], vec![None, Some(true)]],
+            vec![vec![Some(true), None], vec![None, Some(true)]],
+            vec![vec![Some(true), Some(false)], vec![None, Some(true)]],
+        ]
+        .into_iter()
+        .map(|board| ReplayFrame {
+            board,
+            cursor: None,
+        })
+        .collect_vec();
+        let svg = vis_animated_inner(
+            2,
+            2,
+            &frames,
+            &hints,
+            &givens,
+            &options,
+            &GifOptions::default(),
+        );
+        // compact でもマスごとに描く
+        assert!(svg.contains(r##"id="cell-0-0""##));
+        assert!(svg.contains(
+            r##"<set attributeName="fill" begin="0.20s" fill="freeze" href="#cell-0-0" to="black"/>"##
+        ));
+        assert!(svg.contains(
+            r##"<set attributeName="visibility" begin="0.40s" fill="freeze" href="#cross-0-1" to="visible"/>"##
+        ));
+        // 最初の盤面に戻すことはない
+        assert_eq!(svg.matches(r##"href="#cell-"##).count(), 1);
+
+        // 1 コマずつ足しても同じ SVG になり、途中で書き出したあとも続けられる
+        let mut encoder = AnimatedEncoder::new(2, 2, hints.clone(), givens.clone(), &options);
+        encoder.push(&frames[0].board, 20);
+        encoder.push(&frames[1].board, 20);
+        assert_eq!(encoder.snapshot(&frames[2].board), svg);
+        encoder.push(&frames[2].board, 200);
+        assert_eq!(encoder.finish(), svg);
+
+        // 最初に決まっているマスだけの問題を見せてから始める
+        let gif_options = GifOptions {
+            intro: Some(100),
+            ..Default::default()
+        };
+        let svg = vis_animated_inner(2, 2, &frames, &hints, &givens, &options, &gif_options);
+        assert!(svg.contains(r##"begin="1.20s" fill="freeze" href="#cell-0-0""##));
+    }
+
+    #[test]
+    fn test_gif_delays() {
+        let options = GifOptions::default();
+        assert_eq!(options.delays(3), vec![20, 20, 200]);
+        assert_eq!(options.delays(0), vec![]);
+        let options = GifOptions {
+            delay: 30,
+            hold: None,
+            ..Default::default()
+        };
+        assert_eq!(options.delays(2), vec![30, 30]);
+        // 次のコマまでの時間。短すぎるものは 2 にする
+        let options = GifOptions {
+            speed: GifSpeed::RealTime(vec![0.0, 1000.0, 1005.0, 2000.0]),
+            ..Default::default()
+        };
+        assert_eq!(options.delays(4), vec![100, 2, 100, 200]);
+        // 合計が `total` になるように分ける
+        let options = GifOptions {
+            speed: GifSpeed::Total(100),
+            hold: Some(50),
+            ..Default::default()
+        };
+        let delays = options.delays(4);
+        assert_eq!(delays, vec![33, 33, 34, 50]);
+        assert_eq!(delays[..3].iter().sum::<u16>(), 100);
+    }
+
+    #[test]
+    fn test_set_given() {
+        let hints = (vec![vec![1], vec![1]], vec![vec![1], vec![1]]);
+        let givens = vec![vec![true, false], vec![false, false]];
+        let mut board = vec![vec![Some(true), None], vec![None, None]];
+        set_inner(0, 0, Some(false), &mut board, &hints, &givens);
+        assert_eq!(board[0][0], Some(true));
+        set_inner(1, 1, Some(true), &mut board, &hints, &givens);
+        assert_eq!(board[1][1], Some(true));
+    }
 }
@@ -1,37 +1,4 @@
-import { gen, gen_seed, vis_grid, vis_gif, vis_board, vis_gaming_boards, vis_cursor, set } from '../pkg';
-
-function get_hints(h: number, w: number, board: Int32Array): [Int32Array, number, number] {
-    const hints = new Array<number>();
-    let max_hints_width = 0;
-    let max_hints_height = 0;
-    for (let y = 0; y < h; y++) {
-        const line = Array<number>();
-        for (let x = 0; x < w;) {
-            let nx = x;
-            while (nx < w && board[y * w + x] == board[y * w + nx]) nx++;
-            if (board[y * w + x]) line.push(nx - x);
-            x = nx;
-        }
-        max_hints_width = Math.max(max_hints_width, line.length);
-        hints.push(line.length);
-        hints.push.apply(hints, line);
-    }
-    for (let x = 0; x < w; x++) {
-        const line = Array<number>();
-        for (let y = 0; y < h;) {
-            let ny = y;
-            while (ny < h && board[y * w + x] == board[ny * w + x]) ny++;
-            if (board[y * w + x]) line.push(ny - y);
-            y = ny;
-        }
-        max_hints_height = Math.max(max_hints_height, line.length);
-        hints.push(line.length);
-        hints.push.apply(hints, line);
-    }
-    const offset_y = Math.max(max_hints_height * 20, 160) + 40;
-    const offset_x = Math.max(max_hints_width * 20, 160) + 40;
-    return [new Int32Array(hints), offset_y, offset_x];
-}
+import { board_hints, BoardLayout, gen_code, GenConstraints, gen_seed, daily_puzzle, today, daily_countdown, vis_grid, AnimatedSvgEncoder, GifEncoder, png_grid, vis_board, vis_board_diff, vis_gaming_boards, vis_cursor, set, Theme } from '../pkg';
 
 class Timer {
     startTime = Date.now();
@@ -59,10 +26,37 @@ class Timer {
 const timer = new Timer();
 
 let N = 5;
-let ans = gen(N, N, BigInt(0));
-let [hints, offset_y, offset_x] = get_hints(N, N, ans);
+const themeSelect = <HTMLSelectElement>document.getElementById("theme")!;
+let themePreset = parseInt(sessionStorage.getItem('theme') || "0");
+let theme: Theme;
+try {
+    theme = Theme.preset(themePreset);
+} catch {
+    // 保存された値が壊れていたり、なくなった配色だったりする
+    themePreset = 0;
+    theme = Theme.preset(themePreset);
+}
+themeSelect.value = `${themePreset}`;
+// dark のときはページの背景も暗くする
+if (themePreset === 1) {
+    document.body.style.background = "#1e1e1e";
+    document.body.style.color = "#e0e0e0";
+}
+themeSelect.onchange = function () {
+    sessionStorage.setItem('theme', themeSelect.value);
+    location.reload();
+}
+let symmetry = 0;
+let pattern = 0;
+// 1 列あたりの手がかりの個数を制限できる
+let maxClues: number | undefined = undefined;
+let ans = gen_code(N, N, "0", symmetry, pattern, 0, new GenConstraints());
+let hints = board_hints(N, N, ans);
+// 盤面を描いたときの配置。カーソルやボタンの位置を決めるのに使う
+let layout: BoardLayout;
 
 let board = new Int32Array;
+let givens = new Int32Array;
 let gamingBoardSvgs: string[];
 let cursor = { x: 0, y: 0 };
 let pre = { x: -1, y: -1, ctrl: false, enter: false, undo: false };
@@ -73,6 +67,63 @@ let cleared = false;
 let gameover = false;
 let started = false;
 let val: boolean | undefined | null = null;
+// プレイ中の盤面を 1 コマずつ GIF にしていく
+let recorder: GifEncoder | undefined;
+// 保存した GIF。次のコマを足すまで使い回す
+let recorded: Uint8Array | undefined;
+// GIF と同じコマを SVG のアニメーションにもしていく
+let svgRecorder: AnimatedSvgEncoder | undefined;
+
+function drawBoard() {
+    const rendered = vis_board(N, N, board, hints, givens, theme);
+    document.getElementById("gyouza")!.innerHTML = rendered.svg();
+    layout = rendered.layout();
+}
+
+// 変わったマスと手がかりの属性だけを書き換える
+function patchBoard(before: Int32Array) {
+    type Patch =
+        { op: "set", id: string, name: string, value: string } |
+        { op: "add", id: string, parent: string, markup: string } |
+        { op: "remove", id: string };
+    const patches: Patch[] = JSON.parse(vis_board_diff(N, N, before, board, hints, givens, theme));
+    for (const patch of patches) {
+        switch (patch.op) {
+            case "set":
+                document.getElementById(patch.id)!.setAttribute(patch.name, patch.value);
+                break;
+            // バツはバツのあるマスにしかないので、要素ごと足したり消したりする
+            case "add":
+                document.getElementById(patch.parent)!.insertAdjacentHTML("beforeend", patch.markup);
+                break;
+            case "remove":
+                document.getElementById(patch.id)?.remove();
+                break;
+        }
+    }
+}
+
+function startRecording() {
+    recorder?.free();
+    svgRecorder?.free();
+    recorded = undefined;
+    const gifTheme = Theme.preset(themePreset);
+    gifTheme.cell_size = Math.floor(200 / N);
+    recorder = new GifEncoder(N, N, hints, givens, gifTheme);
+    svgRecorder = new AnimatedSvgEncoder(N, N, hints, givens, theme);
+}
+
+// 盤面を変える前に、今の盤面とカーソルを 1 コマ足す
+function recordFrame() {
+    if (recorder === undefined) return;
+    recorder.push_frame(board, cursor.y * N + cursor.x, 20);
+    svgRecorder?.push_frame(board, 20);
+    recorded = undefined;
+}
+
+function drawCursor() {
+    document.getElementById("sushi")!.innerHTML = vis_cursor(N, N, cursor.y, cursor.x, layout, theme);
+}
 
 function isCorrect(board: Int32Array, ans: Int32Array) {
     for (let i = 0; i < N * N; i++) {
@@ -96,22 +147,22 @@ document.onkeydown = function (ev: KeyboardEvent) {
 
     if (!cleared && ev.key == KEY_LEFT) {
         cursor.x = (cursor.x - 1 + N) % N;
-        document.getElementById("sushi")!.innerHTML = vis_cursor(N, N, cursor.y, cursor.x, offset_y, offset_x);
+        drawCursor();
         pre.enter = false;
     }
     if (!cleared && ev.key == KEY_RIGHT) {
         cursor.x = (cursor.x + 1 + N) % N;
-        document.getElementById("sushi")!.innerHTML = vis_cursor(N, N, cursor.y, cursor.x, offset_y, offset_x);
+        drawCursor();
         pre.enter = false;
     }
     if (!cleared && ev.key == KEY_UP) {
         cursor.y = (cursor.y - 1 + N) % N;
-        document.getElementById("sushi")!.innerHTML = vis_cursor(N, N, cursor.y, cursor.x, offset_y, offset_x);
+        drawCursor();
         pre.enter = false;
     }
     if (!cleared && ev.key == KEY_DOWN) {
         cursor.y = (cursor.y + 1 + N) % N;
-        document.getElementById("sushi")!.innerHTML = vis_cursor(N, N, cursor.y, cursor.x, offset_y, offset_x);
+        drawCursor();
         pre.enter = false;
     }
     if (!cleared && ev.key == 'Enter') {
@@ -126,11 +177,13 @@ document.onkeydown = function (ev: KeyboardEvent) {
             if (val === null) val = !ev.ctrlKey && board[cursor.y * N + cursor.x] !== TRUE ? true
                 : ev.ctrlKey && board[cursor.y * N + cursor.x] !== FALSE ? false
                     : undefined;
-            board = set(cursor.y, cursor.x, val, N, N, board, hints);
-            document.getElementById("gyouza")!.innerHTML = vis_board(N, N, board, hints, offset_y, offset_x);
+            const before = board;
+            recordFrame();
+            board = set(cursor.y, cursor.x, val, N, N, board, hints, givens);
+            patchBoard(before);
             pressEnter = true;
             pre.enter = true;
-            if (isHardMode.checked && val !== undefined && ans[cursor.y * N + cursor.x] !== +val) {
+            if (isHardMode.checked && val !== undefined && !givens.includes(cursor.y * N + cursor.x) && ans[cursor.y * N + cursor.x] !== +val) {
                 gameover = true;
                 timer.stop();
                 showGameover();
@@ -143,18 +196,22 @@ document.onkeydown = function (ev: KeyboardEvent) {
         if (undoHistory.length == 0) return;
         if (!pre.undo) redoHistory = [];
         redoHistory.push([board, cursor]);
+        recordFrame();
+        const before = board;
         [board, cursor] = undoHistory.pop()!;
-        document.getElementById("gyouza")!.innerHTML = vis_board(N, N, board, hints, offset_y, offset_x);
-        document.getElementById("sushi")!.innerHTML = vis_cursor(N, N, cursor.y, cursor.x, offset_y, offset_x);
+        patchBoard(before);
+        drawCursor();
         pre.x = cursor.x, pre.y = cursor.y, pre.ctrl = false, pre.undo = true;
     }
 
     if (ev.key == KEY_REDO && ev.ctrlKey) {
         if (redoHistory.length == 0) return;
         undoHistory.push([board, cursor]);
+        recordFrame();
+        const before = board;
         [board, cursor] = redoHistory.pop()!;
-        document.getElementById("gyouza")!.innerHTML = vis_board(N, N, board, hints, offset_y, offset_x);
-        document.getElementById("sushi")!.innerHTML = vis_cursor(N, N, cursor.y, cursor.x, offset_y, offset_x);
+        patchBoard(before);
+        drawCursor();
         pre.x = cursor.x, pre.y = cursor.y, pre.ctrl = false, pre.undo = false;
     }
 
@@ -175,7 +232,7 @@ document.onkeydown = function (ev: KeyboardEvent) {
         cleared = true;
         timer.stop();
         if (isGamingMode.checked) {
-            gamingBoardSvgs = vis_gaming_boards(N, N, board, hints, offset_y, offset_x).split("$");
+            gamingBoardSvgs = vis_gaming_boards(N, N, board, hints, givens, theme).split("$");
             let t = 0;
             function drawGaming() {
                 if (cleared) {
@@ -208,14 +265,19 @@ const sizeSelect = <HTMLSelectElement>document.getElementById("size")!;
 const copyButton = document.getElementById("copy")!;
 const isGamingMode = <HTMLInputElement>document.getElementById("gaming")!;
 const isHardMode = <HTMLInputElement>document.getElementById("hard")!;
+const isConflictsMode = <HTMLInputElement>document.getElementById("conflicts")!;
 const isTimeAttackMode = <HTMLInputElement>document.getElementById("time_attack")!;
 const clock = document.getElementById("clock")!;
 const nextButtton = document.getElementById("next")!;
 const savePngButton = document.getElementById("save_png")!;
 const saveGifButton = <HTMLButtonElement>document.getElementById("save_gif")!;
+const saveSvgButton = document.getElementById("save_svg")!;
 const shareButton = document.getElementById("share")!;
 const nextHardButton = <HTMLButtonElement>document.getElementById("next_hard")!;
 const retryButton = <HTMLButtonElement>document.getElementById("retry")!;
+const dailyButton = document.getElementById("daily")!;
+const dailyCountdown = document.getElementById("daily_countdown")!;
+let dailyLabel = '';
 
 seedInput.onchange = function () {
     const seed = seedInput.value;
@@ -234,6 +296,9 @@ copyButton.onclick = function () {
     const seed = seedInput.value;
     url.searchParams.set('size', `${N}`);
     url.searchParams.set('seed', `${seed}`);
+    if (symmetry !== 0) url.searchParams.set('symmetry', `${symmetry}`);
+    if (pattern !== 0) url.searchParams.set('pattern', `${pattern}`);
+    if (maxClues !== undefined) url.searchParams.set('max_clues', `${maxClues}`);
     navigator.clipboard.writeText(url.toString()).then(function () {
         /* clipboard successfully set */
     }, function () {
@@ -255,6 +320,12 @@ isHardMode.onclick = function () {
         sessionStorage.setItem('hard', 'false');
     }
 }
+// 手がかりと矛盾している列の手がかりを赤くする
+isConflictsMode.onclick = function () {
+    sessionStorage.setItem('conflicts', isConflictsMode.checked ? 'true' : 'false');
+    theme.highlight_conflicts = isConflictsMode.checked;
+    drawBoard();
+}
 isTimeAttackMode.onclick = function () {
     if (isTimeAttackMode.checked) {
         sessionStorage.setItem('timeAttack', 'true');
@@ -267,18 +338,28 @@ isTimeAttackMode.onclick = function () {
 }
 
 
-function newGame(seed: BigInt) {
-    ans = gen(N, N, seed);
-    [hints, offset_y, offset_x] = get_hints(N, N, ans);
+function newGame(seed: string) {
+    const constraints = new GenConstraints(maxClues);
+    try {
+        ans = gen_code(N, N, seed, symmetry, pattern, pattern === 0 ? 0 : 0.7, constraints);
+    } catch {
+        // 小さい盤面では滑らかさの条件を満たせないことがある
+        ans = gen_code(N, N, seed, symmetry, pattern, 0, constraints);
+    }
+    hints = board_hints(N, N, ans);
+    // gen_code の盤面は手がかりだけで解けるので、最初から決まっているマスはない
+    givens = new Int32Array;
     board = new Int32Array(N * N).fill(2);
+    for (const i of givens) board[i] = ans[i];
     cursor = { x: 0, y: 0 };
     pre = { x: 0, y: 0, ctrl: false, enter: false, undo: false };
     pressEnter = false;
     undoHistory = new Array<[Int32Array, { x: number, y: number }]>([board, { x: 0, y: 0 }]);
     redoHistory = new Array<[Int32Array, { x: number, y: number }]>();
 
-    document.getElementById("gyouza")!.innerHTML = vis_board(N, N, board, hints, offset_y, offset_x);
-    document.getElementById("sushi")!.innerHTML = vis_cursor(N, N, 0, 0, offset_y, offset_x);
+    drawBoard();
+    drawCursor();
+    startRecording();
     cleared = false;
     gameover = false;
     started = false;
@@ -290,31 +371,40 @@ function newGame(seed: BigInt) {
 function hideAll() {
     document.getElementById("foot")!.style.visibility = 'hidden';
     document.getElementById("foot")!.style.position = 'relative';
-    document.getElementById("foot")!.style.top = `${N * 24 + 160}px`;
+    document.getElementById("foot")!.style.top = `${layout.height - 50}px`;
 
     document.getElementById("commands")!.style.position = 'relative';
-    document.getElementById("commands")!.style.top = `${N * 24 + 230}px`;
+    document.getElementById("commands")!.style.top = `${layout.height + 20}px`;
 
     document.getElementById("gameover")!.style.visibility = 'hidden';
     document.getElementById("gameover")!.style.position = 'relative';
-    document.getElementById("gameover")!.style.top = `${N * 24 + 70}px`;
+    document.getElementById("gameover")!.style.top = `${layout.height - 140}px`;
 }
 
 function showFoot() {
     document.getElementById("foot")!.style.visibility = 'visible';
-    document.getElementById("commands")!.style.top = `${N * 24 + 340}px`;
+    document.getElementById("commands")!.style.top = `${layout.height + 130}px`;
 }
 
 function showGameover() {
     document.getElementById("gameover")!.style.visibility = 'visible';
-    document.getElementById("commands")!.style.top = `${N * 24 + 270}px`;
+    document.getElementById("commands")!.style.top = `${layout.height + 60}px`;
 }
 
 function load() {
     const url = new URL(location.toString());
     N = parseInt(url.searchParams.get('size') || "10");
-    const seed = url.searchParams.get('seed') || gen_seed();
-    if (!url.searchParams.has('size') || !url.searchParams.has('seed')) {
+    symmetry = parseInt(url.searchParams.get('symmetry') || "0");
+    pattern = parseInt(url.searchParams.get('pattern') || "0");
+    maxClues = url.searchParams.has('max_clues') ? parseInt(url.searchParams.get('max_clues')!) : undefined;
+    let seed = url.searchParams.get('seed') || gen_seed();
+    if (url.searchParams.has('daily')) {
+        const daily = daily_puzzle(url.searchParams.get('daily') || today(Date.now()), 0);
+        N = daily.size();
+        seed = daily.code();
+        dailyLabel = `${daily.date()} (${daily.difficulty()})`;
+        dailyCountdown.textContent = dailyLabel;
+    } else if (!url.searchParams.has('size') || !url.searchParams.has('seed')) {
         url.searchParams.set('size', N.toString());
         url.searchParams.set('seed', seed);
         location.href = url.toString();
@@ -323,14 +413,16 @@ function load() {
     sizeSelect.options[N / 5 - 1].selected = true;
     isGamingMode.checked = sessionStorage.getItem('gaming') === 'true';
     isHardMode.checked = sessionStorage.getItem('hard') === 'true';
+    isConflictsMode.checked = sessionStorage.getItem('conflicts') === 'true';
+    theme.highlight_conflicts = isConflictsMode.checked;
     isTimeAttackMode.checked = sessionStorage.getItem('timeAttack') === 'true';
     if (isTimeAttackMode.checked) {
         clock.style.visibility = 'visible';
     } else {
         clock.style.visibility = 'hidden';
     }
+    newGame(seed);
     hideAll();
-    newGame(BigInt(seed));
 }
 load();
 window.onload = load;
@@ -347,49 +439,56 @@ nextHardButton.onclick = function () {
     url.searchParams.set('seed', seed);
     location.href = url.toString();
 }
+dailyButton.onclick = function () {
+    const url = new URL(location.toString());
+    url.search = '';
+    url.searchParams.set('daily', today(Date.now()));
+    location.href = url.toString();
+}
+setInterval(function () {
+    if (dailyLabel === '') return;
+    const rest = daily_countdown(Date.now());
+    const pad = (n: number) => `${Math.floor(n)}`.padStart(2, '0');
+    dailyCountdown.textContent = `${dailyLabel} next: ${pad(rest / 3600)}:${pad(rest / 60 % 60)}:${pad(rest % 60)}`;
+}, 1000);
 retryButton.onclick = function () {
     const seed = seedInput.value;
+    newGame(seed);
     hideAll();
-    newGame(BigInt(seed));
 }
 savePngButton.onclick = function () {
-    const svgData = vis_grid(N, N, 15, board);
-    const svg = new DOMParser().parseFromString(svgData, "image/svg+xml").getElementById("vis");
-    const canvas = document.createElement("canvas");
-    canvas.width = Number(svg?.getAttribute("width"));
-    canvas.height = Number(svg?.getAttribute("height"));
-    const ctx = canvas.getContext("2d")!;
-    const image = new Image;
-    image.onload = function () {
-        ctx.drawImage(image, 0, 0);
-        const a = document.createElement("a");
-        const seed = seedInput.value;
-        a.href = canvas.toDataURL("image/png");
-        a.download = `${seed}.png`;
-        a.click();
-    }
-    image.src = "data:image/svg+xml;charset=utf-8;base64," + Buffer.from(svgData).toString();
+    const pngTheme = new Theme();
+    pngTheme.cell_size = 15;
+    const pngData = png_grid(N, N, board, pngTheme);
+    const a = document.createElement("a");
+    const seed = seedInput.value;
+    a.href = URL.createObjectURL(new Blob([pngData], { type: "image/png" }));
+    a.download = `${seed}.png`;
+    a.click();
 }
 
 saveGifButton.onclick = function () {
-    const boards = Int32Array.from(undoHistory.slice(1).map((his) => Array.from(his[0])).flat().concat(...board));
-    const d = Math.floor(200 / N);
-    const gifData = vis_gif(N, N, d, boards, undoHistory.length);
-    saveGifButton.disabled = true;
-    saveGifButton.value = "Generating GIF...";
-
-    const image = new Image;
-    image.onload = function () {
-        const a = document.createElement("a");
-        const blob = new Blob([gifData], { type: 'image/gif' });
-        a.href = URL.createObjectURL(blob);
-        const seed = seedInput.value;
-        a.download = `${seed}.gif`;
-        a.click();
-        saveGifButton.disabled = false;
-        saveGifButton.value = "Save as Animation GIF";
+    if (recorder === undefined) return;
+    if (recorded === undefined) {
+        // 最後のコマにはカーソルを描かず、長めに止める。記録はこのあとも続ける
+        recorded = recorder.snapshot(board, 200);
     }
-    image.src = "data:image/jpg;charset=utf-8;base64," + Buffer.from([...gifData.slice(0, 38), 0x3b]).toString('base64');
+    const a = document.createElement("a");
+    const blob = new Blob([recorded], { type: 'image/gif' });
+    a.href = URL.createObjectURL(blob);
+    const seed = seedInput.value;
+    a.download = `${seed}.gif`;
+    a.click();
+}
+saveSvgButton.onclick = function () {
+    if (svgRecorder === undefined) return;
+    // GIF と同じく、undo で戻した手も含めて押した順に流す
+    const svg = svgRecorder.snapshot(board);
+    const a = document.createElement("a");
+    a.href = URL.createObjectURL(new Blob([svg], { type: "image/svg+xml" }));
+    const seed = seedInput.value;
+    a.download = `${seed}.svg`;
+    a.click();
 }
 shareButton.onclick = function () {
     const seed = seedInput.value;